@@ -2,14 +2,14 @@ extern crate gym;
 extern crate binary_brain;
 
 use binary_brain::{train, BinaryBrain, Activation};
+use binary_brain::encode::{Encoders, Linear};
 use std::io;
 
 // model params
-const INPUT_COUNT: usize = 4;
 const OUTPUT_COUNT: usize = 1;
 const TOTAL_COUNT: usize = 64;
 
-const CYCLE_COUNT: usize = 1;
+const MAX_CYCLES: usize = 1;
 
 // genetic trainer params
 const POPULATION_SIZE: usize = 256;
@@ -25,11 +25,12 @@ fn main() {
     io::stdin().read_line(&mut response).unwrap();
     response.pop();
     
+    let encoders = observation_encoders();
     let mut model;
     let is_trained;
     if response.is_empty() {
         is_trained = true;
-        let initial = BinaryBrain::new(INPUT_COUNT, OUTPUT_COUNT, TOTAL_COUNT).unwrap();
+        let initial = BinaryBrain::new(encoders.neuron_count(), OUTPUT_COUNT, TOTAL_COUNT).unwrap();
         let mut trainer = train::Genetic::new(initial, POPULATION_SIZE, TOURNAMENT_SIZE, MUTATION).unwrap();
         
         println!("starting training...");
@@ -38,17 +39,15 @@ fn main() {
         
         'train: for i in 0..MAX_GENERATIONS {
             let evaluation = |brain: &mut BinaryBrain| {
-                let mut input = map_observation(env.reset().unwrap());
+                let mut input = map_observation(&encoders, env.reset().unwrap());
                 let mut output = Vec::with_capacity(OUTPUT_COUNT);
                 let mut fitness = 0.0;
     
                 loop {
-                    for _ in 0..CYCLE_COUNT {
-                        brain.cycle(&input, &mut output).unwrap();
-                    }
+                    brain.cycle_until_stable(&input, &mut output, MAX_CYCLES).unwrap();
                     let action = if output[0].0 {gym::SpaceData::DISCRETE(0)} else {gym::SpaceData::DISCRETE(1)};
                     let state = env.step(&action).unwrap();
-                    input = map_observation(state.observation);
+                    input = map_observation(&encoders, state.observation);
                     fitness += state.reward;
                     if state.is_done {
                         break;
@@ -94,17 +93,17 @@ fn main() {
 
     let gym = gym::GymClient::default();
     let env = gym.make("CartPole-v1");
-    let mut input = map_observation(env.reset().unwrap());
+    let mut input = map_observation(&encoders, env.reset().unwrap());
     let mut output = vec![];
     let mut fitness = 0.0; 
     
     loop {
-        model.cycle(&input, &mut output).unwrap();
+        model.cycle_until_stable(&input, &mut output, MAX_CYCLES).unwrap();
         let action = if output[0].0 {gym::SpaceData::DISCRETE(0)} else {gym::SpaceData::DISCRETE(1)};
         let state = env.step(&action).unwrap();
         env.render();
         std::thread::sleep(std::time::Duration::from_millis(20));
-        input = map_observation(state.observation);
+        input = map_observation(&encoders, state.observation);
         fitness += state.reward;
         if state.is_done {
             break;
@@ -129,26 +128,31 @@ fn main() {
     }
 }
 
-fn map_observation(observation: gym::SpaceData) -> [Activation; INPUT_COUNT] {
-    let vec = observation.get_box().unwrap();
-
-    [
+fn observation_encoders() -> Encoders {
+    Encoders::new()
         // cart position (negative is left, positive is right)
         // the allowed range is [-4.8, 4.8] but since the cart is almost never
         // out that far, we prefer better resolution near the center
-        Activation(((vec[0] / 2.4) * 127.0) as i8),
+        .with(Linear::new(-2.4, 2.4).unwrap())
 
         // cart velocity (- left, + right)
         // theoretically this has infinite range but practically it will
         // never go outside this range, so long as the environment is not forced to continue
-        Activation(((vec[1] / 3.0) * 127.0) as i8),
-        
+        .with(Linear::new(-3.0, 3.0).unwrap())
+
         // pole angle in radians (- left, + right)
         // this is the allowed range, if the pole tips outside it the env terminates
-        Activation(((vec[2] / 0.418) * 127.0) as i8),
-        
+        .with(Linear::new(-0.418, 0.418).unwrap())
+
         // pole angular velocity
         // again, technically infinite
-        Activation(((vec[3] / 4.5) * 127.0) as i8),
-    ]
-}
\ No newline at end of file
+        .with(Linear::new(-4.5, 4.5).unwrap())
+}
+
+fn map_observation(encoders: &Encoders, observation: gym::SpaceData) -> Vec<Activation> {
+    let vec = observation.get_box().unwrap();
+
+    let mut input = Vec::with_capacity(encoders.neuron_count());
+    encoders.encode(&vec.to_vec(), &mut input).unwrap();
+    input
+}
@@ -0,0 +1,248 @@
+use crate::*;
+
+impl BinaryBrain {
+    /// Finds groups of hidden neurons with the same incoming weights and activations, which always get the same sum
+    /// and so fire together once they have been updated at the same time. Groups are sorted by their first neuron.
+    /// Only supported by `Connectivity::Dense` and `Connectivity::Symmetric` without layers.
+    pub fn duplicate_neurons(&self) -> Result<Vec<Vec<usize>>> {
+        self.check_addressable()?;
+        if self.layers.is_some() {
+            return Err(BinaryBrainError::UnsupportedDynamics);
+        }
+
+        let output_start = self.neuron_count - self.output_count;
+        let mut groups: HashMap<(Vec<NeuronChunk>, i8, Option<i8>), Vec<usize>> = HashMap::new();
+        for i in self.input_count..output_start {
+            let mut row = vec![NeuronChunk::default(); self.values.len()];
+            for j in 0..self.neuron_count {
+                set_bit(&mut row, j, self.weight(i, j));
+            }
+            let off_act = self.off_act.as_ref().map(|off_act| off_act[i].0);
+            groups.entry((row, self.act[i].0, off_act)).or_default().push(i);
+        }
+
+        let mut duplicates: Vec<Vec<usize>> = groups.into_values().filter(|group| group.len() > 1).collect();
+        duplicates.sort_unstable_by_key(|group| group[0]);
+        Ok(duplicates)
+    }
+
+    /// Merges every group of duplicate neurons, see `duplicate_neurons`, into fewer neurons of the group whose outgoing
+    /// weights add up to the same contribution as the whole group. Since weights are +1 or -1, a group can shrink to
+    /// as many neurons as the largest net contribution it makes to a single neuron, so a pair with opposite outgoing
+    /// weights disappears and a group with mostly agreeing outgoing weights stays as it is.
+    /// Like `prune`, neurons are removed in multiples of 64 and only as many as possible while the merged brain
+    /// fires the same outputs with the same distance to their activations over `inputs`, which needs deterministic dynamics,
+    /// because duplicates only fire together once they have been updated at the same time.
+    /// Only supported by `Connectivity::Dense` without layers, and groups with excitatory or inhibitory neurons are left alone
+    /// since their outgoing weights can't change.
+    pub fn merge_duplicates(&mut self, inputs: &[Vec<Activation>]) -> Result<PruneReport> {
+        self.check_deterministic()?;
+        if !matches!(self.connectivity, Connectivity::Dense) {
+            return Err(BinaryBrainError::UnsupportedConnectivity);
+        }
+
+        let groups: Vec<Vec<usize>> = self.duplicate_neurons()?
+            .into_iter()
+            .filter(|group| group.iter().all(|&i| self.neuron_type(i) == NeuronType::Free))
+            .collect();
+
+        // the neurons a group needs have the same parity as the group, so it shrinks two neurons at a time
+        let mut candidates = Vec::new();
+        for group in groups.iter() {
+            let needed = (0..self.neuron_count)
+                .map(|i| self.net_weight(i, group).unsigned_abs() as usize)
+                .max()
+                .unwrap_or(0);
+            candidates.extend(group[needed..].iter().rev());
+        }
+
+        let chunk_size = size_of::<NeuronChunk>() * 8;
+        let mut report = PruneReport {
+            removed: Vec::new(),
+            neurons_before: self.neuron_count,
+            neurons_after: self.neuron_count,
+            weight_chunks_before: self.weight_matrix.len(),
+            weight_chunks_after: self.weight_matrix.len(),
+        };
+
+        let mut count = candidates.len() / chunk_size * chunk_size;
+        while count > 0 {
+            let merged = self.without_duplicates(&groups, &candidates[..count])?;
+
+            if self.same_outputs(&merged, inputs)? {
+                report.removed = candidates[..count].to_vec();
+                report.neurons_after = merged.neuron_count;
+                report.weight_chunks_after = merged.weight_matrix.len();
+                *self = merged;
+                break;
+            }
+
+            count -= chunk_size;
+        }
+
+        Ok(report)
+    }
+
+    /// A copy of this brain without the given duplicates, where the remaining neurons of every group
+    /// take over the net contribution of their group.
+    fn without_duplicates(&self, groups: &[Vec<usize>], removed: &[usize]) -> Result<BinaryBrain> {
+        let mut is_removed = vec![false; self.neuron_count];
+        for &i in removed.iter() {
+            is_removed[i] = true;
+        }
+
+        let mut merged = self.clone();
+        for group in groups.iter() {
+            let kept: Vec<usize> = group.iter().copied().filter(|&i| !is_removed[i]).collect();
+            if kept.len() == group.len() {
+                continue;
+            }
+
+            for i in 0..self.neuron_count {
+                let positive = ((kept.len() as i32 + self.net_weight(i, group)) / 2) as usize;
+                for (rank, &k) in kept.iter().enumerate() {
+                    set_bit(&mut merged.weight_matrix, i * self.neuron_count + k, rank < positive);
+                }
+            }
+        }
+
+        merged.remove_neurons(removed)?;
+        Ok(merged)
+    }
+
+    /// The sum of the weights from the neurons of `group` to neuron `to`.
+    fn net_weight(&self, to: usize, group: &[usize]) -> i32 {
+        group.iter().map(|&from| if self.weight(to, from) { 1 } else { -1 }).sum()
+    }
+
+    /// Sorts the hidden neurons into an order that only depends on how they are wired, not on their index,
+    /// so two brains that only differ in the labelling of their hidden neurons end up identical.
+    /// Inputs and outputs keep their place, and duplicate neurons end up next to each other.
+    /// Neurons are told apart by their parameters and then by refining with the neurons they are connected to,
+    /// which distinguishes the neurons of practically every brain that isn't constructed to be symmetric.
+    /// The update order moves along with the neurons, so a brain with `UpdateOrder::Sequential` ends up with the
+    /// `UpdateOrder::Permutation` that updates its neurons in the same sequence as before, unless that is index order.
+    /// Only supported by `Connectivity::Dense` and `Connectivity::Symmetric` without layers, and not by
+    /// `UpdateOrder::Shuffled`, which draws orders of indices that can't be moved along.
+    pub fn canonicalize(&mut self) -> Result<()> {
+        self.check_addressable()?;
+        if self.layers.is_some() {
+            return Err(BinaryBrainError::UnsupportedDynamics);
+        }
+        match self.update_order {
+            UpdateOrder::Shuffled(_) => return Err(BinaryBrainError::UnsupportedDynamics),
+            UpdateOrder::Sequential => self.update_order = UpdateOrder::Permutation((0..self.neuron_count).collect()),
+            UpdateOrder::Permutation(_) | UpdateOrder::Synchronous => (),
+        }
+
+        let output_start = self.neuron_count - self.output_count;
+        let sources: Vec<Option<usize>> = (0..self.input_count)
+            .chain(self.canonical_order())
+            .chain(output_start..self.neuron_count)
+            .map(Some)
+            .collect();
+
+        self.remap(&sources, self.input_count, self.output_count, NeuronInit::Random, &mut thread_rng())?;
+
+        if let UpdateOrder::Permutation(permutation) = &self.update_order {
+            if permutation.iter().enumerate().all(|(position, &i)| position == i) {
+                self.update_order = UpdateOrder::Sequential;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// A hash of the parameters and configuration of the canonical form of this brain, see `canonicalize`.
+    /// Brains that only differ in their state or in the labelling of their hidden neurons, with their update order
+    /// following the labels, have the same hash.
+    pub fn canonical_hash(&self) -> Result<u64> {
+        let mut canonical = self.clone();
+        canonical.canonicalize()?;
+
+        let mut bytes = Vec::new();
+        canonical.write_to(&mut bytes).unwrap();
+
+        Ok(bytes.chunks(8).fold(bytes.len() as u64, |hash, chunk| {
+            let mut word = [0; 8];
+            word[..chunk.len()].copy_from_slice(chunk);
+            mix(hash ^ u64::from_le_bytes(word))
+        }))
+    }
+
+    /// The hidden neurons in canonical order.
+    fn canonical_order(&self) -> Vec<usize> {
+        let output_start = self.neuron_count - self.output_count;
+        let hidden = self.input_count..output_start;
+
+        let mut rank = vec![0; self.neuron_count];
+        if let UpdateOrder::Permutation(permutation) = &self.update_order {
+            for (position, &i) in permutation.iter().enumerate() {
+                rank[i] = position;
+            }
+        }
+
+        // inputs and outputs are told apart by their index, hidden neurons start out with their own parameters
+        let mut colors: Vec<u64> = (0..self.neuron_count).map(|i| {
+            if !hidden.contains(&i) {
+                return mix(i as u64);
+            }
+            let off_act = self.off_act.as_ref().map_or(0, |off_act| 0x100 | off_act[i].0 as u8 as u64);
+            let parameters = [
+                self.act[i].0 as u8 as u64,
+                off_act,
+                self.neuron_type(i) as u64,
+                self.weight(i, i) as u64,
+                rank[i] as u64,
+            ];
+            parameters.iter().fold(u64::MAX, |color, &parameter| mix(color ^ parameter))
+        }).collect();
+
+        let distinct = |colors: &[u64]| {
+            let mut hidden_colors = colors[hidden.clone()].to_vec();
+            hidden_colors.sort_unstable();
+            hidden_colors.dedup();
+            hidden_colors.len()
+        };
+
+        // every round a neuron also takes in the multiset of colors it is connected to, until no more neurons split off
+        let mut distinct_count = distinct(&colors);
+        loop {
+            let refined: Vec<u64> = (0..self.neuron_count).map(|i| {
+                if !hidden.contains(&i) {
+                    return colors[i];
+                }
+                let neighbours = (0..self.neuron_count)
+                    .filter(|&j| j != i)
+                    .map(|j| mix(colors[j] ^ (self.weight(i, j) as u64) ^ ((self.weight(j, i) as u64) << 1)))
+                    .fold(0u64, u64::wrapping_add);
+                mix(colors[i] ^ mix(neighbours))
+            }).collect();
+
+            let refined_count = distinct(&refined);
+            colors = refined;
+            if refined_count == distinct_count {
+                break;
+            }
+            distinct_count = refined_count;
+        }
+
+        let mut order: Vec<usize> = hidden.collect();
+        order.sort_unstable_by_key(|&i| (colors[i], i));
+        order
+    }
+
+    fn check_addressable(&self) -> Result<()> {
+        match self.connectivity {
+            Connectivity::Dense | Connectivity::Symmetric => Ok(()),
+            _ => Err(BinaryBrainError::UnsupportedConnectivity),
+        }
+    }
+}
+
+/// The splitmix64 finalizer, spreads every bit of `x` over the whole result.
+fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
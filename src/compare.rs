@@ -0,0 +1,146 @@
+use crate::*;
+use std::hash::{Hash, Hasher};
+
+/// How far apart the parameters of two brains are, see `BinaryBrain::distance`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct Distance {
+    /// The amount of weights that differ.
+    pub weights: u64,
+    /// The sum of the absolute differences of the activations and off activations.
+    pub activations: u64,
+}
+
+/// Which parameters differ between two brains, see `BinaryBrain::diff`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct BrainDiff {
+    /// Rows of the weight matrix with changed weights along with the amount of changed weights.
+    /// A row holds the incoming weights of a neuron, or of a group with `Connectivity::SharedGrid`.
+    /// With `Connectivity::Symmetric` a row only holds the weights from neurons with a lower index.
+    pub rows: Vec<(usize, u32)>,
+    /// Neurons with a changed activation along with the old and new activation.
+    pub activations: Vec<(usize, Activation, Activation)>,
+    /// Like `activations`, a brain without off activations uses its activations instead
+    /// and this is empty when neither brain has off activations.
+    pub off_activations: Vec<(usize, Activation, Activation)>,
+    /// Neurons with a changed `NeuronType`.
+    pub neuron_types: Vec<usize>,
+}
+
+/// Brains are equal when their parameters and configuration are equal, their state is not compared.
+impl PartialEq for BinaryBrain {
+    fn eq(&self, other: &BinaryBrain) -> bool {
+        self.input_count == other.input_count
+            && self.output_count == other.output_count
+            && self.neuron_count == other.neuron_count
+            && self.connectivity == other.connectivity
+            && self.weight_matrix == other.weight_matrix
+            && self.act == other.act
+            && self.off_act == other.off_act
+            && self.type_mask == other.type_mask
+            && self.type_sign == other.type_sign
+            && self.update_order == other.update_order
+            && self.firing_mode == other.firing_mode
+            && self.neuron_model == other.neuron_model
+            && self.layers == other.layers
+    }
+}
+
+// the floats in the firing mode and neuron model are validated to not be NaN
+impl Eq for BinaryBrain {}
+
+impl Hash for BinaryBrain {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.input_count.hash(state);
+        self.output_count.hash(state);
+        self.weight_matrix.hash(state);
+        self.act.hash(state);
+        self.off_act.hash(state);
+        self.type_mask.hash(state);
+        self.type_sign.hash(state);
+    }
+}
+
+impl BinaryBrain {
+    /// The distance between the parameters of two brains with the same connectivity and amount of neurons.
+    pub fn distance(&self, other: &BinaryBrain) -> Result<Distance> {
+        self.check_comparable(other)?;
+
+        let weights = self.weight_matrix.iter().zip(other.weight_matrix.iter())
+            .map(|(a, b)| (a.0 ^ b.0).count_ones() as u64)
+            .sum();
+
+        let (off_act, other_off_act) = self.off_activation_pair(other);
+        let activations = self.act.iter().zip(other.act.iter())
+            .chain(off_act.iter().zip(other_off_act.iter()))
+            .map(|(a, b)| (a.0 as i64 - b.0 as i64).unsigned_abs())
+            .sum();
+
+        Ok(Distance {
+            weights,
+            activations,
+        })
+    }
+
+    /// The parameters that differ between this brain and `other`, which need the same connectivity and amount of neurons.
+    pub fn diff(&self, other: &BinaryBrain) -> Result<BrainDiff> {
+        self.check_comparable(other)?;
+
+        let mut rows = Vec::new();
+        for (row, range) in self.weight_rows().into_iter().enumerate() {
+            let changed: u32 = self.weight_matrix[range.clone()].iter().zip(other.weight_matrix[range].iter())
+                .map(|(a, b)| (a.0 ^ b.0).count_ones())
+                .sum();
+            if changed > 0 {
+                rows.push((row, changed));
+            }
+        }
+
+        let changed = |a: &[Activation], b: &[Activation]| -> Vec<(usize, Activation, Activation)> {
+            (0..a.len()).filter(|&i| a[i] != b[i]).map(|i| (i, a[i], b[i])).collect()
+        };
+        let (off_act, other_off_act) = self.off_activation_pair(other);
+
+        Ok(BrainDiff {
+            rows,
+            activations: changed(&self.act, &other.act),
+            off_activations: changed(off_act, other_off_act),
+            neuron_types: (0..self.neuron_count).filter(|&i| self.neuron_type(i) != other.neuron_type(i)).collect(),
+        })
+    }
+
+    fn check_comparable(&self, other: &BinaryBrain) -> Result<()> {
+        if self.connectivity != other.connectivity || self.neuron_count != other.neuron_count {
+            return Err(BinaryBrainError::MismatchedBrains);
+        }
+
+        Ok(())
+    }
+
+    /// The off activations to compare, which are empty when neither brain has them
+    /// and fall back to the activations for a brain without them.
+    fn off_activation_pair<'a>(&'a self, other: &'a BinaryBrain) -> (&'a [Activation], &'a [Activation]) {
+        match (&self.off_act, &other.off_act) {
+            (None, None) => (&[], &[]),
+            (off_act, other_off_act) => (off_act.as_ref().unwrap_or(&self.act), other_off_act.as_ref().unwrap_or(&other.act)),
+        }
+    }
+
+    /// The chunks of every row of the weight matrix.
+    fn weight_rows(&self) -> Vec<std::ops::Range<usize>> {
+        let chunk_size = size_of::<NeuronChunk>() * 8;
+
+        match &self.connectivity {
+            Connectivity::Dense => {
+                let row_size = self.neuron_count / chunk_size;
+                (0..self.neuron_count).map(|i| i * row_size..(i + 1) * row_size).collect()
+            }
+            Connectivity::Symmetric => {
+                (0..self.neuron_count).map(|i| triangle_offset(i)..triangle_offset(i) + i.div_ceil(chunk_size)).collect()
+            }
+            Connectivity::Grid { radius, .. } | Connectivity::SharedGrid { radius, .. } => {
+                let row_size = grid_row_size(*radius);
+                (0..self.connectivity.grid_row_count(self.neuron_count)).map(|i| i * row_size..(i + 1) * row_size).collect()
+            }
+        }
+    }
+}
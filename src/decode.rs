@@ -0,0 +1,202 @@
+use crate::*;
+
+/// Turns the output of a cycle into an action.
+pub trait Decoder {
+    type Action;
+
+    /// The amount of output neurons the decoder reads, which is the output count a brain needs.
+    fn output_count(&self) -> usize;
+
+    /// Decodes the output of a cycle, which needs at least `output_count` outputs.
+    fn decode(&self, output: &[(bool, i32)]) -> Result<Self::Action>;
+}
+
+/// Picks the output with the highest sum, the first one when several outputs share it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Argmax {
+    pub outputs: usize,
+}
+
+/// Splits the outputs into `groups` groups of `group_size` neurons, where every group stands for an action.
+/// The group with the most firing neurons wins, ties are broken by the sum of the group and then by order.
+/// Averaging over many neurons makes the decision less sensitive to single neurons.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Population {
+    pub groups: usize,
+    pub group_size: usize,
+}
+
+/// Maps the sum of output `output` linearly to a continuous action, `low` and below map to `min`
+/// and `high` and above map to `max`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Linear {
+    output: usize,
+    low: i32,
+    high: i32,
+    min: f64,
+    max: f64,
+}
+
+impl Population {
+    /// The fraction of firing neurons in every group.
+    pub fn activity(&self, output: &[(bool, i32)]) -> Result<Vec<f64>> {
+        check_output(self, output)?;
+
+        Ok(output.chunks(self.group_size).take(self.groups)
+            .map(|group| group.iter().filter(|(fire, _)| *fire).count() as f64 / self.group_size as f64)
+            .collect())
+    }
+}
+
+impl Linear {
+    pub fn new(output: usize, low: i32, high: i32, min: f64, max: f64) -> Result<Linear> {
+        if low >= high || !min.is_finite() || !max.is_finite() {
+            return Err(BinaryBrainError::InvalidDecoding);
+        }
+
+        Ok(Linear { output, low, high, min, max })
+    }
+
+    pub fn output(&self) -> usize {
+        self.output
+    }
+
+    pub fn low(&self) -> i32 {
+        self.low
+    }
+
+    pub fn high(&self) -> i32 {
+        self.high
+    }
+
+    pub fn min(&self) -> f64 {
+        self.min
+    }
+
+    pub fn max(&self) -> f64 {
+        self.max
+    }
+}
+
+impl Decoder for Argmax {
+    type Action = usize;
+
+    fn output_count(&self) -> usize {
+        self.outputs
+    }
+
+    fn decode(&self, output: &[(bool, i32)]) -> Result<usize> {
+        check_output(self, output)?;
+
+        let mut best = 0;
+        for (i, &(_, sum)) in output.iter().enumerate().take(self.outputs) {
+            if sum > output[best].1 {
+                best = i;
+            }
+        }
+
+        Ok(best)
+    }
+}
+
+impl Decoder for Population {
+    type Action = usize;
+
+    fn output_count(&self) -> usize {
+        self.groups * self.group_size
+    }
+
+    fn decode(&self, output: &[(bool, i32)]) -> Result<usize> {
+        check_output(self, output)?;
+
+        let mut best = (0, 0, i64::MIN);
+        for (i, group) in output.chunks(self.group_size).take(self.groups).enumerate() {
+            let fired = group.iter().filter(|(fire, _)| *fire).count();
+            let sum = group.iter().map(|&(_, sum)| sum as i64).sum();
+            if (fired, sum) > (best.1, best.2) {
+                best = (i, fired, sum);
+            }
+        }
+
+        Ok(best.0)
+    }
+}
+
+impl Decoder for Linear {
+    type Action = f64;
+
+    fn output_count(&self) -> usize {
+        self.output + 1
+    }
+
+    fn decode(&self, output: &[(bool, i32)]) -> Result<f64> {
+        check_output(self, output)?;
+
+        // in f64 since the distances between sums can exceed the range of an i32
+        let fraction = (output[self.output].1 as f64 - self.low as f64) / (self.high as f64 - self.low as f64);
+        Ok(self.min + fraction.clamp(0.0, 1.0) * (self.max - self.min))
+    }
+}
+
+/// Counts how often every output fires over several cycles, which turns binary outputs into firing rates.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Rate {
+    counts: Vec<u32>,
+    cycles: u32,
+}
+
+impl Rate {
+    pub fn new(output_count: usize) -> Rate {
+        Rate {
+            counts: vec![0; output_count],
+            cycles: 0,
+        }
+    }
+
+    /// Adds the output of a cycle.
+    pub fn record(&mut self, output: &[(bool, i32)]) -> Result<()> {
+        if output.len() != self.counts.len() {
+            return Err(BinaryBrainError::WrongOutputShape);
+        }
+
+        for (count, &(fire, _)) in self.counts.iter_mut().zip(output.iter()) {
+            *count += fire as u32;
+        }
+        self.cycles += 1;
+
+        Ok(())
+    }
+
+    /// Cycles `brain` `cycles` times with the same input and records every output.
+    pub fn record_cycles(&mut self, brain: &mut BinaryBrain, input: &[Activation], cycles: usize) -> Result<()> {
+        let mut output = Vec::with_capacity(brain.output_count());
+        for _ in 0..cycles {
+            brain.cycle(input, &mut output)?;
+            self.record(&output)?;
+        }
+
+        Ok(())
+    }
+
+    /// The fraction of recorded cycles in which every output fired, or 0 before anything was recorded.
+    pub fn rates(&self) -> Vec<f64> {
+        self.counts.iter().map(|&count| count as f64 / self.cycles.max(1) as f64).collect()
+    }
+
+    pub fn cycles(&self) -> u32 {
+        self.cycles
+    }
+
+    pub fn reset(&mut self) {
+        self.counts.iter_mut().for_each(|count| *count = 0);
+        self.cycles = 0;
+    }
+}
+
+fn check_output<D: Decoder>(decoder: &D, output: &[(bool, i32)]) -> Result<()> {
+    if output.len() < decoder.output_count() || decoder.output_count() == 0 {
+        return Err(BinaryBrainError::WrongOutputShape);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,246 @@
+use crate::*;
+
+/// Turns a real value into the input of a fixed amount of input neurons.
+/// Inputs are added to the sums of the input neurons, so the strength of an encoder
+/// decides how hard an input neuron is pushed towards firing or staying silent.
+pub trait Encoder {
+    /// The amount of input neurons the encoded value takes up.
+    fn neuron_count(&self) -> usize;
+
+    /// Appends the input for `value` to `input`. Values outside the range of the encoder are clamped to it.
+    fn encode(&self, value: f64, input: &mut Vec<Activation>);
+}
+
+/// Maps the range linearly onto the range of a single activation, which is -127 at `min` and 127 at `max`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Linear {
+    min: f64,
+    max: f64,
+}
+
+/// Splits the range into `neurons` equal steps and pushes a neuron towards firing for every step below the value,
+/// so neighbouring values share most of their input.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Thermometer {
+    min: f64,
+    max: f64,
+    neurons: usize,
+    strength: Activation,
+}
+
+/// Splits the range into `buckets` equal buckets and only pushes the neuron of the bucket holding the value towards firing.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct OneHot {
+    min: f64,
+    max: f64,
+    buckets: usize,
+    strength: Activation,
+}
+
+/// Splits the range into `2^bits` equal steps and encodes the step of the value as a Gray code,
+/// where neighbouring steps differ in a single neuron.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Gray {
+    min: f64,
+    max: f64,
+    bits: usize,
+    strength: Activation,
+}
+
+impl Linear {
+    pub fn new(min: f64, max: f64) -> Result<Linear> {
+        validate_range(min, max, 1)?;
+        Ok(Linear { min, max })
+    }
+
+    pub fn min(&self) -> f64 {
+        self.min
+    }
+
+    pub fn max(&self) -> f64 {
+        self.max
+    }
+}
+
+impl Thermometer {
+    /// Creates a thermometer encoder with the strongest possible input.
+    pub fn new(min: f64, max: f64, neurons: usize) -> Result<Thermometer> {
+        validate_range(min, max, neurons)?;
+        Ok(Thermometer { min, max, neurons, strength: Activation(i8::MAX) })
+    }
+
+    pub fn min(&self) -> f64 {
+        self.min
+    }
+
+    pub fn max(&self) -> f64 {
+        self.max
+    }
+
+    pub fn strength(&self) -> Activation {
+        self.strength
+    }
+
+    /// Sets the activation an input neuron receives, negated for neurons that should stay silent.
+    pub fn set_strength(&mut self, strength: Activation) {
+        self.strength = strength;
+    }
+}
+
+impl OneHot {
+    /// Creates a one-hot encoder with the strongest possible input.
+    pub fn new(min: f64, max: f64, buckets: usize) -> Result<OneHot> {
+        validate_range(min, max, buckets)?;
+        Ok(OneHot { min, max, buckets, strength: Activation(i8::MAX) })
+    }
+
+    pub fn min(&self) -> f64 {
+        self.min
+    }
+
+    pub fn max(&self) -> f64 {
+        self.max
+    }
+
+    pub fn strength(&self) -> Activation {
+        self.strength
+    }
+
+    /// Sets the activation an input neuron receives, negated for neurons that should stay silent.
+    pub fn set_strength(&mut self, strength: Activation) {
+        self.strength = strength;
+    }
+}
+
+impl Gray {
+    /// Creates a Gray code encoder with the strongest possible input. At most 63 bits are supported.
+    pub fn new(min: f64, max: f64, bits: usize) -> Result<Gray> {
+        validate_range(min, max, bits)?;
+        if bits >= 64 {
+            return Err(BinaryBrainError::InvalidEncoding);
+        }
+        Ok(Gray { min, max, bits, strength: Activation(i8::MAX) })
+    }
+
+    pub fn min(&self) -> f64 {
+        self.min
+    }
+
+    pub fn max(&self) -> f64 {
+        self.max
+    }
+
+    pub fn strength(&self) -> Activation {
+        self.strength
+    }
+
+    /// Sets the activation an input neuron receives, negated for neurons that should stay silent.
+    pub fn set_strength(&mut self, strength: Activation) {
+        self.strength = strength;
+    }
+}
+
+impl Encoder for Linear {
+    fn neuron_count(&self) -> usize {
+        1
+    }
+
+    fn encode(&self, value: f64, input: &mut Vec<Activation>) {
+        let scaled = normalize(value, self.min, self.max) * 254.0 - 127.0;
+        input.push(Activation(scaled.round() as i8));
+    }
+}
+
+impl Encoder for Thermometer {
+    fn neuron_count(&self) -> usize {
+        self.neurons
+    }
+
+    fn encode(&self, value: f64, input: &mut Vec<Activation>) {
+        let level = normalize(value, self.min, self.max) * self.neurons as f64;
+        input.extend((0..self.neurons).map(|i| signed(self.strength, level > i as f64)));
+    }
+}
+
+impl Encoder for OneHot {
+    fn neuron_count(&self) -> usize {
+        self.buckets
+    }
+
+    fn encode(&self, value: f64, input: &mut Vec<Activation>) {
+        let bucket = step(value, self.min, self.max, self.buckets);
+        input.extend((0..self.buckets).map(|i| signed(self.strength, i == bucket)));
+    }
+}
+
+impl Encoder for Gray {
+    fn neuron_count(&self) -> usize {
+        self.bits
+    }
+
+    fn encode(&self, value: f64, input: &mut Vec<Activation>) {
+        let level = step(value, self.min, self.max, 1 << self.bits) as u64;
+        let code = level ^ (level >> 1);
+        input.extend((0..self.bits).map(|bit| signed(self.strength, code & (1 << bit) != 0)));
+    }
+}
+
+/// Encodes several values, each with its own encoder, into the input of one brain.
+#[derive(Default)]
+pub struct Encoders {
+    encoders: Vec<Box<dyn Encoder>>,
+}
+
+impl Encoders {
+    pub fn new() -> Encoders {
+        Encoders::default()
+    }
+
+    /// Adds an encoder for the next value.
+    pub fn with<E: Encoder + 'static>(mut self, encoder: E) -> Encoders {
+        self.encoders.push(Box::new(encoder));
+        self
+    }
+
+    /// The amount of input neurons all values take up, which is the input count a brain needs.
+    pub fn neuron_count(&self) -> usize {
+        self.encoders.iter().map(|encoder| encoder.neuron_count()).sum()
+    }
+
+    /// Replaces `input` with the encoded `values`, which need one value per encoder.
+    pub fn encode(&self, values: &[f64], input: &mut Vec<Activation>) -> Result<()> {
+        if values.len() != self.encoders.len() {
+            return Err(BinaryBrainError::WrongInputShape);
+        }
+
+        input.clear();
+        for (encoder, &value) in self.encoders.iter().zip(values.iter()) {
+            encoder.encode(value, input);
+        }
+
+        Ok(())
+    }
+}
+
+fn validate_range(min: f64, max: f64, neurons: usize) -> Result<()> {
+    if !min.is_finite() || !max.is_finite() || min >= max || neurons == 0 {
+        return Err(BinaryBrainError::InvalidEncoding);
+    }
+
+    Ok(())
+}
+
+/// The position of `value` in the range as a fraction from 0 to 1, NaN is treated as `min`.
+fn normalize(value: f64, min: f64, max: f64) -> f64 {
+    let fraction = ((value - min) / (max - min)).clamp(0.0, 1.0);
+    if fraction.is_nan() { 0.0 } else { fraction }
+}
+
+/// The index of the step holding `value` when the range is split into `steps` equal steps.
+fn step(value: f64, min: f64, max: f64, steps: usize) -> usize {
+    ((normalize(value, min, max) * steps as f64) as usize).min(steps - 1)
+}
+
+fn signed(strength: Activation, positive: bool) -> Activation {
+    if positive { strength } else { Activation(strength.0.saturating_neg()) }
+}
@@ -0,0 +1,125 @@
+use crate::*;
+use crate::graph::{read_module, write_module};
+use std::io::Write;
+
+/// How an `Ensemble` combines the outputs of its members.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Vote {
+    /// An output fires when more than half of the members fire it.
+    /// The reported sum is the amount of members that fired minus the amount that didn't.
+    #[default]
+    Majority,
+    /// The reported sum is the average sum of the members, rounded down.
+    /// An output fires when it is above the average activation of the output neuron,
+    /// off activations and stochastic firing are not taken into account.
+    AverageSum,
+}
+
+/// Several brains with the same inputs and outputs that are cycled together and vote on the output.
+#[derive(Debug, Clone)]
+pub struct Ensemble {
+    members: Vec<BinaryBrain>,
+    vote: Vote,
+    outputs: Vec<(bool, i32)>,
+}
+
+impl Ensemble {
+    pub fn new(members: Vec<BinaryBrain>, vote: Vote) -> Result<Ensemble> {
+        if members.is_empty() {
+            return Err(BinaryBrainError::InvalidPopSize);
+        }
+        let (input_count, output_count) = (members[0].input_count(), members[0].output_count());
+        if members.iter().any(|member| member.input_count() != input_count || member.output_count() != output_count) {
+            return Err(BinaryBrainError::MismatchedBrains);
+        }
+
+        Ok(Ensemble {
+            members,
+            vote,
+            outputs: Vec::new(),
+        })
+    }
+
+    pub fn members(&self) -> &[BinaryBrain] {
+        &self.members
+    }
+
+    pub fn vote(&self) -> Vote {
+        self.vote
+    }
+
+    pub fn set_vote(&mut self, vote: Vote) {
+        self.vote = vote;
+    }
+
+    pub fn input_count(&self) -> usize {
+        self.members[0].input_count()
+    }
+
+    pub fn output_count(&self) -> usize {
+        self.members[0].output_count()
+    }
+
+    /// Cycles every member once with `input` and combines their outputs, see `Vote`.
+    pub fn cycle(&mut self, input: &[Activation], output: &mut Vec<(bool, i32)>) -> Result<()> {
+        let output_count = self.output_count();
+        let mut votes = vec![0i32; output_count];
+        let mut sums = vec![0i64; output_count];
+        let mut activations = vec![0i64; output_count];
+
+        for member in self.members.iter_mut() {
+            member.cycle(input, &mut self.outputs)?;
+
+            let output_start = member.activations().len() - output_count;
+            for (k, &(fire, sum)) in self.outputs.iter().enumerate() {
+                votes[k] += if fire { 1 } else { -1 };
+                sums[k] += sum as i64;
+                activations[k] += member.activations()[output_start + k].0 as i64;
+            }
+        }
+
+        let count = self.members.len() as i64;
+        output.clear();
+        output.extend((0..output_count).map(|k| match self.vote {
+            Vote::Majority => (votes[k] > 0, votes[k]),
+            Vote::AverageSum => (sums[k] > activations[k], sums[k].div_euclid(count) as i32),
+        }));
+
+        Ok(())
+    }
+
+    /// Reads an ensemble written by `write_to_file`.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Ensemble> {
+        let mut file = io::BufReader::new(fs::File::open(path)?);
+
+        let vote = match file.read_u8()? {
+            0 => Vote::Majority,
+            1 => Vote::AverageSum,
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "unknown vote")),
+        };
+        let member_count = file.read_u64::<LittleEndian>()?;
+        let mut members = Vec::new();
+        for _ in 0..member_count {
+            members.push(read_module(&mut file)?);
+        }
+
+        Ensemble::new(members, vote)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid members"))
+    }
+
+    /// Writes the vote and every member into a single file.
+    pub fn write_to_file<P: AsRef<Path>>(&self, target: P) -> io::Result<()> {
+        let mut file = io::BufWriter::new(fs::File::create(target)?);
+
+        file.write_u8(match self.vote {
+            Vote::Majority => 0,
+            Vote::AverageSum => 1,
+        })?;
+        file.write_u64::<LittleEndian>(self.members.len() as u64)?;
+        for member in self.members.iter() {
+            write_module(&mut file, member)?;
+        }
+
+        file.flush()
+    }
+}
@@ -0,0 +1,212 @@
+use crate::*;
+use std::io::{Read, Write};
+
+/// How the output of a neuron is turned into the input of another module.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Signal {
+    /// The input is the given activation when the output neuron fired and its negation otherwise.
+    Fire(Activation),
+    /// The input is the sum of the output neuron, clamped to the range of an activation.
+    Sum,
+}
+
+/// Connects output `from_output` of module `from` to input `to_input` of module `to`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Wire {
+    pub from: usize,
+    pub from_output: usize,
+    pub to: usize,
+    pub to_input: usize,
+    pub signal: Signal,
+}
+
+/// Several brains wired together, which are cycled as one.
+/// Modules are cycled in the order they were added and a wire carries the latest output of its module,
+/// so wires from earlier modules deliver the output of the same cycle, while wires from the module itself
+/// or later modules deliver the output of the previous cycle. Inputs that receive several wires get the sum of their signals.
+#[derive(Debug, Clone, Default)]
+pub struct BrainGraph {
+    modules: Vec<BinaryBrain>,
+    wires: Vec<Wire>,
+    outputs: Vec<Vec<(bool, i32)>>,
+    inputs: Vec<Activation>,
+}
+
+impl BrainGraph {
+    pub fn new() -> BrainGraph {
+        BrainGraph::default()
+    }
+
+    /// Adds a module and returns its index.
+    pub fn add_module(&mut self, brain: BinaryBrain) -> usize {
+        self.outputs.push(vec![(false, 0); brain.output_count()]);
+        self.modules.push(brain);
+        self.modules.len() - 1
+    }
+
+    pub fn connect(&mut self, wire: Wire) -> Result<()> {
+        self.check_wire(&wire)?;
+
+        self.wires.push(wire);
+        Ok(())
+    }
+
+    pub fn modules(&self) -> &[BinaryBrain] {
+        &self.modules
+    }
+
+    /// Gives access to a module, for example to train it. Its input and output count should stay the same,
+    /// `cycle` fails while a wire no longer fits the module.
+    pub fn module_mut(&mut self, module: usize) -> Option<&mut BinaryBrain> {
+        self.modules.get_mut(module)
+    }
+
+    pub fn wires(&self) -> &[Wire] {
+        &self.wires
+    }
+
+    /// The inputs of the graph are the inputs of its modules that are not connected to a wire, in module order.
+    pub fn input_count(&self) -> usize {
+        self.modules.iter().enumerate()
+            .map(|(m, module)| (0..module.input_count()).filter(|&i| !self.is_wired(m, i)).count())
+            .sum()
+    }
+
+    /// The outputs of the graph are the outputs of all modules in module order, whether they are wired or not.
+    pub fn output_count(&self) -> usize {
+        self.modules.iter().map(|module| module.output_count()).sum()
+    }
+
+    /// Cycles every module once, see `BrainGraph` for the order.
+    pub fn cycle(&mut self, input: &[Activation], output: &mut Vec<(bool, i32)>) -> Result<()> {
+        for wire in self.wires.iter() {
+            self.check_wire(wire)?;
+        }
+        if input.len() != self.input_count() {
+            return Err(BinaryBrainError::WrongInputShape);
+        }
+        for (module, outputs) in self.modules.iter().zip(self.outputs.iter_mut()) {
+            outputs.resize(module.output_count(), (false, 0));
+        }
+
+        let mut external = input.iter();
+        for m in 0..self.modules.len() {
+            let input_count = self.modules[m].input_count();
+            let mut sums = vec![0i32; input_count];
+            let mut wired = vec![false; input_count];
+            for wire in self.wires.iter().filter(|wire| wire.to == m) {
+                let (fire, sum) = self.outputs[wire.from][wire.from_output];
+                sums[wire.to_input] += match wire.signal {
+                    Signal::Fire(strength) if fire => strength.0 as i32,
+                    Signal::Fire(strength) => -(strength.0 as i32),
+                    Signal::Sum => sum,
+                };
+                wired[wire.to_input] = true;
+            }
+
+            self.inputs.clear();
+            for (sum, wired) in sums.into_iter().zip(wired) {
+                let value = if wired { sum } else { external.next().unwrap().0 as i32 };
+                self.inputs.push(Activation(value.clamp(i8::MIN as i32, i8::MAX as i32) as i8));
+            }
+
+            self.modules[m].cycle(&self.inputs, &mut self.outputs[m])?;
+        }
+
+        output.clear();
+        output.extend(self.outputs.iter().flatten());
+
+        Ok(())
+    }
+
+    fn check_wire(&self, wire: &Wire) -> Result<()> {
+        if wire.from >= self.modules.len() || wire.to >= self.modules.len() {
+            return Err(BinaryBrainError::InvalidModuleIndex);
+        }
+        if wire.from_output >= self.modules[wire.from].output_count() || wire.to_input >= self.modules[wire.to].input_count() {
+            return Err(BinaryBrainError::InvalidNeuronIndex);
+        }
+
+        Ok(())
+    }
+
+    fn is_wired(&self, module: usize, input: usize) -> bool {
+        self.wires.iter().any(|wire| wire.to == module && wire.to_input == input)
+    }
+
+    /// Reads a graph written by `write_to_file`. The outputs of the previous cycle that wires carry start out empty.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<BrainGraph> {
+        let mut file = io::BufReader::new(fs::File::open(path)?);
+        let invalid = |_| io::Error::new(io::ErrorKind::InvalidData, "invalid wire");
+
+        let mut graph = BrainGraph::new();
+        let module_count = file.read_u64::<LittleEndian>()?;
+        for _ in 0..module_count {
+            let brain = read_module(&mut file)?;
+            graph.add_module(brain);
+        }
+
+        let wire_count = file.read_u64::<LittleEndian>()?;
+        for _ in 0..wire_count {
+            let from = file.read_u64::<LittleEndian>()? as usize;
+            let from_output = file.read_u64::<LittleEndian>()? as usize;
+            let to = file.read_u64::<LittleEndian>()? as usize;
+            let to_input = file.read_u64::<LittleEndian>()? as usize;
+            let signal = match file.read_u8()? {
+                0 => Signal::Fire(Activation(file.read_i8()?)),
+                1 => Signal::Sum,
+                _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "unknown signal")),
+            };
+            graph.connect(Wire { from, from_output, to, to_input, signal }).map_err(invalid)?;
+        }
+
+        Ok(graph)
+    }
+
+    /// Writes every module followed by the wires into a single file.
+    pub fn write_to_file<P: AsRef<Path>>(&self, target: P) -> io::Result<()> {
+        let mut file = io::BufWriter::new(fs::File::create(target)?);
+
+        file.write_u64::<LittleEndian>(self.modules.len() as u64)?;
+        for module in self.modules.iter() {
+            write_module(&mut file, module)?;
+        }
+
+        file.write_u64::<LittleEndian>(self.wires.len() as u64)?;
+        for wire in self.wires.iter() {
+            file.write_u64::<LittleEndian>(wire.from as u64)?;
+            file.write_u64::<LittleEndian>(wire.from_output as u64)?;
+            file.write_u64::<LittleEndian>(wire.to as u64)?;
+            file.write_u64::<LittleEndian>(wire.to_input as u64)?;
+            match wire.signal {
+                Signal::Fire(strength) => {
+                    file.write_u8(0)?;
+                    file.write_i8(strength.0)?;
+                }
+                Signal::Sum => file.write_u8(1)?,
+            }
+        }
+
+        file.flush()
+    }
+}
+
+/// Writes a brain prefixed with its length in bytes, since a brain is read until the end of its data.
+pub(crate) fn write_module<W: io::Write>(writer: &mut W, brain: &BinaryBrain) -> io::Result<()> {
+    let mut bytes = Vec::new();
+    brain.write_to(&mut bytes)?;
+
+    writer.write_u64::<LittleEndian>(bytes.len() as u64)?;
+    writer.write_all(&bytes)
+}
+
+pub(crate) fn read_module<R: io::Read>(reader: &mut R) -> io::Result<BinaryBrain> {
+    let length = reader.read_u64::<LittleEndian>()?;
+    let mut bytes = Vec::new();
+    reader.take(length).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 != length {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated module"));
+    }
+
+    BinaryBrain::read_from(&mut bytes.as_slice())
+}
@@ -0,0 +1,164 @@
+use crate::*;
+use crate::resize::weight_position;
+
+/// How `BinaryBrain::new_with_init` chooses the initial weights and activations.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Init {
+    pub weights: WeightInit,
+    pub activations: ActivationInit,
+}
+
+/// The initial weights of a new brain. Binary weights can't be zero,
+/// so structured weights alternate between +1 and -1 where a real valued net would have no weight.
+/// Every strategy but `Random` is only supported by `Connectivity::Dense` and `Connectivity::Symmetric`.
+/// With `Connectivity::Symmetric` the weights to a neuron from neurons with a higher index mirror
+/// the weights chosen in the other direction.
+#[derive(Debug, Clone, PartialEq)]
+pub enum WeightInit {
+    /// Every weight is +1 with probability `density`, which has to be in the range [0, 1].
+    Random { density: f32 },
+    /// The weight from neuron `j` is +1 when `j` is even, so the sum over a state where
+    /// every pair of neurons `2k` and `2k + 1` agrees is 0.
+    Alternating,
+    /// Like `Alternating`, but the weights from neuron `i` and its partner `i ^ 1` to `i` are +1.
+    /// Over a state where every pair agrees the sum of a neuron is twice its own state,
+    /// so with activations from -2 to 1 every neuron keeps its state.
+    /// Only supported by `Connectivity::Dense`, since the mirrored weights of `Connectivity::Symmetric`
+    /// don't cancel out and it has no weight from a neuron to itself.
+    Identity,
+    /// Random weights from every layer to the next one and alternating weights everywhere else.
+    /// The layers are given by their non-zero amount of neurons in index order,
+    /// where the first layer holds exactly the inputs and the last layer exactly the outputs.
+    /// `BinaryBrain::set_layers` with the same layers masks the other weights out.
+    Layered(Vec<usize>),
+}
+
+impl Default for WeightInit {
+    fn default() -> WeightInit {
+        WeightInit::Random { density: 0.5 }
+    }
+}
+
+/// The initial activations of a new brain.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum ActivationInit {
+    /// Uniformly random over the whole range of an activation.
+    #[default]
+    Uniform,
+    /// Uniformly random within `spread` standard deviations of the sum of a neuron with random weights and states,
+    /// which is the square root of the amount of weights to the neuron, rounded and clamped to the range of an activation.
+    /// Small brains get activations their sums can actually reach this way.
+    Scaled { spread: f32 },
+    Constant(Activation),
+}
+
+impl Init {
+    pub(crate) fn validate(&self, connectivity: &Connectivity, input_count: usize, output_count: usize, total_count: usize) -> Result<()> {
+        if let ActivationInit::Scaled { spread } = self.activations {
+            if !spread.is_finite() {
+                return Err(BinaryBrainError::InvalidInit);
+            }
+        }
+
+        match &self.weights {
+            WeightInit::Random { density } if !(0.0..=1.0).contains(density) => return Err(BinaryBrainError::InvalidInit),
+            WeightInit::Random { .. } => return Ok(()),
+            WeightInit::Layered(layers) => validate_layers(layers, input_count, output_count, total_count)?,
+            WeightInit::Identity if *connectivity != Connectivity::Dense => return Err(BinaryBrainError::UnsupportedConnectivity),
+            _ => (),
+        }
+
+        match connectivity {
+            Connectivity::Dense | Connectivity::Symmetric => Ok(()),
+            _ => Err(BinaryBrainError::UnsupportedConnectivity),
+        }
+    }
+}
+
+impl WeightInit {
+    pub(crate) fn generate<R: Rng + ?Sized>(&self, connectivity: &Connectivity, total_count: usize, rng: &mut R) -> Vec<NeuronChunk> {
+        let weight_chunks = connectivity.weight_chunk_count(total_count);
+
+        let density = match *self {
+            WeightInit::Random { density } => density,
+            _ => return self.generate_structured(connectivity, total_count, rng),
+        };
+        if density == 0.5 {
+            return repeat_with(|| NeuronChunk(rng.gen())).take(weight_chunks).collect();
+        }
+
+        let mut weights = vec![NeuronChunk::default(); weight_chunks];
+        for position in 0..weight_chunks * size_of::<NeuronChunk>() * 8 {
+            set_bit(&mut weights, position, rng.gen::<f32>() < density);
+        }
+
+        weights
+    }
+
+    fn generate_structured<R: Rng + ?Sized>(&self, connectivity: &Connectivity, total_count: usize, rng: &mut R) -> Vec<NeuronChunk> {
+        let mut layer = vec![0; total_count];
+        if let WeightInit::Layered(layers) = self {
+            let mut start = 0;
+            for (index, &size) in layers.iter().enumerate() {
+                layer[start..start + size].iter_mut().for_each(|l| *l = index);
+                start += size;
+            }
+        }
+
+        let mut weights = vec![NeuronChunk::default(); connectivity.weight_chunk_count(total_count)];
+        for to in 0..total_count {
+            for from in 0..total_count {
+                let position = match weight_position(connectivity, total_count, to, from) {
+                    Some(position) => position,
+                    None => continue,
+                };
+
+                let weight = match self {
+                    WeightInit::Identity if to | 1 == from | 1 => true,
+                    WeightInit::Layered(_) if layer[to] == layer[from] + 1 => rng.gen(),
+                    _ => from % 2 == 0,
+                };
+                set_bit(&mut weights, position, weight);
+            }
+        }
+
+        weights
+    }
+}
+
+impl ActivationInit {
+    pub(crate) fn generate<R: Rng + ?Sized>(&self, connectivity: &Connectivity, total_count: usize, rng: &mut R) -> Vec<Activation> {
+        match *self {
+            ActivationInit::Uniform => repeat_with(|| Activation(rng.gen())).take(total_count).collect(),
+            ActivationInit::Scaled { spread } => {
+                let fan_in = match connectivity {
+                    Connectivity::Dense => total_count,
+                    Connectivity::Symmetric => total_count - 1,
+                    Connectivity::Grid { radius, .. } | Connectivity::SharedGrid { radius, .. } => (2 * radius + 1) * (2 * radius + 1),
+                };
+                // in f64 so a large spread can't overflow the range
+                let range = (spread as f64 * (fan_in as f64).sqrt()).abs();
+
+                repeat_with(|| {
+                    let activation = if range > 0.0 { rng.gen_range(-range, range) } else { 0.0 };
+                    Activation(activation.round().clamp(i8::MIN as f64, i8::MAX as f64) as i8)
+                }).take(total_count).collect()
+            }
+            ActivationInit::Constant(activation) => vec![activation; total_count],
+        }
+    }
+}
+
+/// Checks that `layers` covers all neurons, starts with the inputs and ends with the outputs.
+pub(crate) fn validate_layers(layers: &[usize], input_count: usize, output_count: usize, total_count: usize) -> Result<()> {
+    // an empty layer would leave the next layer without sources, so nothing could pass through
+    if layers.len() < 2
+        || layers.contains(&0)
+        || layers[0] != input_count
+        || layers[layers.len() - 1] != output_count
+        || layers.iter().try_fold(0usize, |sum, &size| sum.checked_add(size)) != Some(total_count) {
+        return Err(BinaryBrainError::InvalidLayers);
+    }
+
+    Ok(())
+}
@@ -5,89 +5,285 @@ extern crate test;
 use rand::prelude::*;
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use std::iter::repeat_with;
+use std::collections::HashMap;
 use std::fs;
 use std::io;
 use std::path::Path;
 use std::mem::size_of;
+use util::Xoshiro128PlusPlusAvx2;
 
 
 pub type Result<T> = std::result::Result<T, BinaryBrainError>;
 
+// brain files that don't use dense connectivity start with this instead of the input count,
+// followed by the connectivity and then the regular header
+const EXTENDED_HEADER: u64 = u64::MAX;
+
+// tags of the optional sections that follow the activations in a brain file
+const SECTION_UPDATE_ORDER: u8 = 1;
+const SECTION_FIRING_MODE: u8 = 2;
+const SECTION_NEURON_MODEL: u8 = 3;
+const SECTION_OFF_ACTIVATIONS: u8 = 4;
+const SECTION_NEURON_TYPES: u8 = 5;
+const SECTION_LAYERS: u8 = 6;
+
+pub mod decode;
+pub mod encode;
 pub mod train;
+mod canonical;
+mod compare;
+mod ensemble;
+mod graph;
+mod init;
+mod patch;
+mod prune;
+mod resize;
 mod util;
 
+pub use compare::{BrainDiff, Distance};
+pub use ensemble::{Ensemble, Vote};
+pub use graph::{BrainGraph, Signal, Wire};
+pub use init::{ActivationInit, Init, WeightInit};
+use init::validate_layers;
+pub use patch::Patch;
+pub use prune::PruneReport;
+pub use resize::NeuronInit;
+
 #[derive(Debug, Clone)]
 pub struct BinaryBrain {
     weight_matrix: Vec<NeuronChunk>,
+    connectivity: Connectivity,
     values: Vec<NeuronChunk>,
     act: Vec<Activation>,
+    off_act: Option<Vec<Activation>>,
     input_count: usize,
     output_count: usize,
     neuron_count: usize,
+    update_order: UpdateOrder,
+    schedule: Vec<usize>,
+    schedule_rng: StdRng,
+    firing_mode: FiringMode,
+    firing_rng: Xoshiro128PlusPlusAvx2,
+    noise: Vec<u32>,
+    // the sums over the weights from higher neurons of a symmetric brain, see `prepare_upper_sums`
+    upper_sums: Vec<i32>,
+    neuron_model: NeuronModel,
+    potential: Vec<f32>,
+    refractory: Vec<u32>,
+    // neurons with their bit set in `type_mask` have all outgoing weights set to their bit in `type_sign`
+    type_mask: Vec<NeuronChunk>,
+    type_sign: Vec<NeuronChunk>,
+    layers: Option<Vec<usize>>,
+    // the range of neurons in the previous layer of every neuron, empty when there are no layers
+    layer_sources: Vec<(usize, usize)>,
 }
 
 impl BinaryBrain {
     pub fn new(input_count: usize, output_count: usize, total_count: usize) -> Result<BinaryBrain> {
+        Self::new_with_connectivity(input_count, output_count, total_count, Connectivity::Dense)
+    }
+
+    pub fn new_with_connectivity(input_count: usize, output_count: usize, total_count: usize, connectivity: Connectivity) -> Result<BinaryBrain> {
+        Self::new_with_rng(input_count, output_count, total_count, connectivity, &mut thread_rng())
+    }
+
+    /// Like `new_with_connectivity`, but draws the weights, activations and the seed for stochastic firing from `rng`,
+    /// so a seeded `rng` always creates the same brain.
+    pub fn new_with_rng<R: Rng + ?Sized>(
+        input_count: usize,
+        output_count: usize,
+        total_count: usize,
+        connectivity: Connectivity,
+        rng: &mut R,
+    ) -> Result<BinaryBrain> {
+        Self::new_with_init(input_count, output_count, total_count, connectivity, &Init::default(), rng)
+    }
+
+    /// Like `new_with_rng`, but chooses the weights and activations as configured by `init`.
+    pub fn new_with_init<R: Rng + ?Sized>(
+        input_count: usize,
+        output_count: usize,
+        total_count: usize,
+        connectivity: Connectivity,
+        init: &Init,
+        rng: &mut R,
+    ) -> Result<BinaryBrain> {
         if total_count % (size_of::<NeuronChunk>() * 8) != 0 {
             return Err(BinaryBrainError::TotalNotDivisbleByChunkSize);
         }
         if input_count + output_count > total_count {
             return Err(BinaryBrainError::InputOutputAboveTotal);
         }
+        connectivity.validate(total_count)?;
+        init.validate(&connectivity, input_count, output_count, total_count)?;
 
-        let mut rng = thread_rng();
-        let mut act = vec![Activation::default(); total_count];
-        for i in 0..act.len() {
-            act[i].0 = rng.gen();
-        }
+        let act = init.activations.generate(&connectivity, total_count, rng);
+        let weights = init.weights.generate(&connectivity, total_count, rng);
 
-        let weight_count = total_count * total_count;
+        let mut brain = Self::from_parts(connectivity, weights, act, input_count, output_count);
+        brain.firing_rng = Xoshiro128PlusPlusAvx2::from_rng(rng);
 
-        Ok(BinaryBrain {
-            weight_matrix: repeat_with(|| NeuronChunk(rng.gen()) ).take(weight_count / (size_of::<NeuronChunk>() * 8)).collect(),
-            values: vec![NeuronChunk::default(); total_count / (size_of::<NeuronChunk>() * 8)],
-            act: act,
-            input_count: input_count,
-            output_count: output_count,
-            neuron_count: total_count,
-        })
+        Ok(brain)
     }
 
+    /// Creates a new random brain with the same shape, connectivity, update order, firing mode, neuron model
+    /// and neuron types as `template`.  
+    /// If `template` has off activations the new brain gets random ones too, each at most its regular activation.
     pub fn from_template(template: &BinaryBrain) -> BinaryBrain {
-        Self::new(
+        Self::from_template_with_rng(template, &mut thread_rng())
+    }
+
+    /// Like `from_template`, but draws the random parameters from `rng`.
+    pub fn from_template_with_rng<R: Rng + ?Sized>(template: &BinaryBrain, rng: &mut R) -> BinaryBrain {
+        let mut brain = Self::new_with_rng(
             template.input_count,
             template.output_count,
-            template.act.len()
-        ).unwrap()
+            template.act.len(),
+            template.connectivity.clone(),
+            rng,
+        ).unwrap();
+        brain.inherit_config(template);
+
+        if template.off_act.is_some() {
+            let off_act = brain.act.iter()
+                .map(|act| Activation(rng.gen_range(i8::MIN as i16, act.0 as i16 + 1) as i8))
+                .collect();
+            brain.set_off_activations(Some(off_act)).unwrap();
+        }
+
+        brain
     }
 
     pub fn with_parameters(weight_matrix: Vec<NeuronChunk>, activations: Vec<Activation>, input_count: usize, output_count: usize) -> Result<BinaryBrain> {
+        Self::with_connectivity(Connectivity::Dense, weight_matrix, activations, input_count, output_count)
+    }
+
+    /// Creates a brain from parameters where the layout of `weight_matrix` is determined by `connectivity`.
+    /// Like every brain that isn't drawn from an `rng`, stochastic firing starts from a fixed seed, see `set_firing_seed`.
+    pub fn with_connectivity(
+        connectivity: Connectivity,
+        weight_matrix: Vec<NeuronChunk>,
+        activations: Vec<Activation>,
+        input_count: usize,
+        output_count: usize,
+    ) -> Result<BinaryBrain> {
         let total_count = activations.len();
         if input_count + output_count > total_count {
             return Err(BinaryBrainError::InputOutputAboveTotal);
         }
-        if weight_matrix.len() != (total_count * total_count) / (size_of::<NeuronChunk>() * 8) {
+        connectivity.validate(total_count)?;
+        if weight_matrix.len() != connectivity.weight_chunk_count(total_count) {
             return Err(BinaryBrainError::InvalidWeightActivationCombo);
         }
 
-        Ok(BinaryBrain {
-            weight_matrix: weight_matrix,
+        Ok(Self::from_parts(connectivity, weight_matrix, activations, input_count, output_count))
+    }
+
+    fn from_parts(
+        connectivity: Connectivity,
+        weight_matrix: Vec<NeuronChunk>,
+        act: Vec<Activation>,
+        input_count: usize,
+        output_count: usize,
+    ) -> BinaryBrain {
+        let total_count = act.len();
+
+        let mut brain = BinaryBrain {
+            weight_matrix,
+            connectivity,
             values: vec![NeuronChunk::default(); total_count / (size_of::<NeuronChunk>() * 8)],
-            act: activations,
-            input_count: input_count,
-            output_count: output_count,
+            act,
+            off_act: None,
+            input_count,
+            output_count,
             neuron_count: total_count,
-        })
+            update_order: UpdateOrder::Sequential,
+            schedule: (0..total_count).collect(),
+            schedule_rng: StdRng::seed_from_u64(0),
+            firing_mode: FiringMode::Threshold,
+            firing_rng: Xoshiro128PlusPlusAvx2::from_rng(&mut StdRng::seed_from_u64(0)),
+            noise: Vec::new(),
+            upper_sums: Vec::new(),
+            neuron_model: NeuronModel::Binary,
+            potential: vec![0.0; total_count],
+            refractory: vec![0; total_count],
+            type_mask: vec![NeuronChunk::default(); total_count / (size_of::<NeuronChunk>() * 8)],
+            type_sign: vec![NeuronChunk::default(); total_count / (size_of::<NeuronChunk>() * 8)],
+            layers: None,
+            layer_sources: Vec::new(),
+        };
+        brain.clear_unused_weights();
+
+        brain
+    }
+
+    /// Clears the bits in `weight_matrix` that don't represent a weight, so equal brains have equal parameters.
+    fn clear_unused_weights(&mut self) {
+        let chunk_size = size_of::<NeuronChunk>() * 8;
+
+        match self.connectivity {
+            Connectivity::Dense => (),
+            Connectivity::Symmetric => {
+                for i in 0..self.neuron_count {
+                    let offset = triangle_offset(i);
+                    if i % chunk_size != 0 {
+                        self.weight_matrix[offset + i / chunk_size].0 &= (1 << (i % chunk_size)) - 1;
+                    }
+                }
+
+                let used = triangle_offset(self.neuron_count);
+                self.weight_matrix[used..].iter_mut().for_each(|chunk| chunk.0 = 0);
+            }
+            Connectivity::Grid { radius, .. } | Connectivity::SharedGrid { radius, .. } => {
+                let neighbours = (2 * radius + 1) * (2 * radius + 1);
+                let row_size = grid_row_size(radius);
+                let row_count = self.connectivity.grid_row_count(self.neuron_count);
+                if neighbours % chunk_size != 0 {
+                    for row in self.weight_matrix.chunks_mut(row_size).take(row_count) {
+                        row[row_size - 1].0 &= (1 << (neighbours % chunk_size)) - 1;
+                    }
+                }
+
+                let used = row_count * row_size;
+                self.weight_matrix[used..].iter_mut().for_each(|chunk| chunk.0 = 0);
+            }
+        }
+    }
+
+    /// Copies everything that configures the brain, but is not a parameter, from `parent`.  
+    /// The weights are adjusted to the neuron types of `parent`.
+    pub(crate) fn inherit_config(&mut self, parent: &BinaryBrain) {
+        self.set_update_order(parent.update_order.clone()).unwrap();
+        self.set_firing_mode(parent.firing_mode).unwrap();
+        self.set_neuron_model(parent.neuron_model).unwrap();
+        self.set_layers(parent.layers.clone()).unwrap();
+
+        self.type_mask = parent.type_mask.clone();
+        self.type_sign = parent.type_sign.clone();
+        self.enforce_neuron_types();
     }
 
     pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<BinaryBrain> {
-        let mut file = fs::File::open(path)?;
+        let mut file = io::BufReader::new(fs::File::open(path)?);
 
-        let input_count = file.read_u64::<LittleEndian>()? as usize;
+        Self::read_from(&mut file)
+    }
+
+    /// Reads a brain in the format of `write_to_file`, consuming the reader until its end.
+    pub(crate) fn read_from<R: io::Read>(file: &mut R) -> io::Result<BinaryBrain> {
+        let mut input_count = file.read_u64::<LittleEndian>()?;
+        let mut connectivity = Connectivity::Dense;
+        if input_count == EXTENDED_HEADER {
+            connectivity = Connectivity::read_from(file)?;
+            input_count = file.read_u64::<LittleEndian>()?;
+        }
+        let input_count = input_count as usize;
         let output_count = file.read_u64::<LittleEndian>()? as usize;
         let total_count = file.read_u64::<LittleEndian>()? as usize;
+        connectivity.validate(total_count)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid connectivity"))?;
 
-        let weight_chunks = (total_count * total_count) / (size_of::<NeuronChunk>() * 8);
+        let weight_chunks = connectivity.weight_chunk_count(total_count);
         let mut weights = Vec::with_capacity(weight_chunks);
         for _ in 0..weight_chunks {
             let chunk = file.read_u64::<LittleEndian>()?;
@@ -100,14 +296,60 @@ impl BinaryBrain {
             act.push(Activation(chunk));
         }
 
-        Ok(BinaryBrain {
-            weight_matrix: weights,
-            values: vec![NeuronChunk::default(); total_count / (size_of::<NeuronChunk>() * 8)],
-            act: act,
-            input_count: input_count,
-            output_count: output_count,
-            neuron_count: total_count,
-        })
+        let mut brain = Self::from_parts(connectivity, weights, act, input_count, output_count);
+
+        loop {
+            let section = match file.read_u8() {
+                Ok(section) => section,
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e),
+            };
+
+            match section {
+                SECTION_UPDATE_ORDER => {
+                    let order = UpdateOrder::read_from(file)?;
+                    brain.set_update_order(order)
+                        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid update order"))?;
+                }
+                SECTION_FIRING_MODE => {
+                    let mode = FiringMode::read_from(file)?;
+                    brain.set_firing_mode(mode)
+                        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid firing mode"))?;
+                }
+                SECTION_NEURON_MODEL => {
+                    let model = NeuronModel::read_from(file)?;
+                    brain.set_neuron_model(model)
+                        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid neuron model"))?;
+                }
+                SECTION_OFF_ACTIVATIONS => {
+                    let mut off_act = Vec::with_capacity(total_count);
+                    for _ in 0..total_count {
+                        off_act.push(Activation(file.read_i8()?));
+                    }
+                    brain.set_off_activations(Some(off_act))
+                        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid off activations"))?;
+                }
+                SECTION_NEURON_TYPES => {
+                    for i in 0..brain.type_mask.len() {
+                        brain.type_mask[i].0 = file.read_u64::<LittleEndian>()?;
+                        brain.type_sign[i].0 = file.read_u64::<LittleEndian>()?;
+                    }
+                    brain.enforce_neuron_types();
+                }
+                SECTION_LAYERS => {
+                    let layer_count = file.read_u64::<LittleEndian>()?;
+                    let mut layers = Vec::new();
+                    for _ in 0..layer_count {
+                        layers.push(file.read_u64::<LittleEndian>()? as usize);
+                    }
+                    brain.set_layers(Some(layers))
+                        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid layers"))?;
+                }
+                _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "unknown section")),
+            }
+        }
+
+        Ok(brain)
     }
 
     pub fn write_to_file<P: AsRef<Path>>(&self, target: P) -> io::Result<()> {
@@ -116,7 +358,16 @@ impl BinaryBrain {
             .truncate(true)
             .create(true)
             .open(target)?;
-        
+
+        self.write_to(&mut file)
+    }
+
+    /// Writes the brain in the format of `write_to_file`.
+    pub(crate) fn write_to<W: io::Write>(&self, file: &mut W) -> io::Result<()> {
+        if self.connectivity != Connectivity::Dense {
+            file.write_u64::<LittleEndian>(EXTENDED_HEADER)?;
+            self.connectivity.write_to(file)?;
+        }
         file.write_u64::<LittleEndian>(self.input_count as u64)?;
         file.write_u64::<LittleEndian>(self.output_count as u64)?;
         file.write_u64::<LittleEndian>(self.neuron_count as u64)?;
@@ -128,6 +379,39 @@ impl BinaryBrain {
         for chunk in self.act.iter() {
             file.write_i8(chunk.0)?;
         }
+
+        if self.update_order != UpdateOrder::Sequential {
+            file.write_u8(SECTION_UPDATE_ORDER)?;
+            self.update_order.write_to(file)?;
+        }
+        if self.firing_mode != FiringMode::Threshold {
+            file.write_u8(SECTION_FIRING_MODE)?;
+            self.firing_mode.write_to(file)?;
+        }
+        if self.neuron_model != NeuronModel::Binary {
+            file.write_u8(SECTION_NEURON_MODEL)?;
+            self.neuron_model.write_to(file)?;
+        }
+        if let Some(off_act) = &self.off_act {
+            file.write_u8(SECTION_OFF_ACTIVATIONS)?;
+            for chunk in off_act.iter() {
+                file.write_i8(chunk.0)?;
+            }
+        }
+        if self.type_mask.iter().any(|chunk| chunk.0 != 0) {
+            file.write_u8(SECTION_NEURON_TYPES)?;
+            for (mask, sign) in self.type_mask.iter().zip(self.type_sign.iter()) {
+                file.write_u64::<LittleEndian>(mask.0)?;
+                file.write_u64::<LittleEndian>(sign.0)?;
+            }
+        }
+        if let Some(layers) = &self.layers {
+            file.write_u8(SECTION_LAYERS)?;
+            file.write_u64::<LittleEndian>(layers.len() as u64)?;
+            for &size in layers.iter() {
+                file.write_u64::<LittleEndian>(size as u64)?;
+            }
+        }
         
         Ok(())
     }
@@ -139,35 +423,302 @@ impl BinaryBrain {
         }
         
         output.clear();
-        output.reserve(self.output_count); 
-        let output_start = self.act.len() - self.output_count;
+        output.resize(self.output_count, (false, 0));
+        self.step(input, None, output);
+
+        Ok(())
+    }
+
+    /// Cycles the brain with the given neurons clamped to a fixed state instead of adding an input bias.  
+    /// Clamped neurons keep their state during the cycle but still influence the other neurons,
+    /// which allows driving the input neurons with bits directly and teacher forcing any other neuron.  
+    /// The output of a clamped output neuron contains the clamped state and the sum it received.
+    pub fn cycle_clamped(&mut self, clamps: &[(usize, bool)], output: &mut Vec<(bool, i32)>) -> Result<()> {
+        if clamps.iter().any(|&(neuron, _)| neuron >= self.neuron_count) {
+            return Err(BinaryBrainError::InvalidNeuronIndex);
+        }
+
+        let mut clamped = vec![NeuronChunk::default(); self.values.len()];
+        for &(neuron, state) in clamps.iter() {
+            set_bit(&mut clamped, neuron, true);
+            set_bit(&mut self.values, neuron, state);
+        }
+
+        output.clear();
+        output.resize(self.output_count, (false, 0));
+        self.step(&[], Some(&clamped), output);
 
-        for (i, v) in self.act.iter().enumerate() {
-            let chunk_size = size_of::<NeuronChunk>() * 8;
-            let mut sum = self.calc_sum(i);
-            
-            if i < self.input_count {
-                sum += input[i].0 as i32;
+        Ok(())
+    }
+
+    /// Updates every neuron once, neurons with a bit set in `clamped` keep their state.
+    #[inline]
+    fn step(&mut self, input: &[Activation], clamped: Option<&[NeuronChunk]>, output: &mut [(bool, i32)]) {
+        self.draw_noise();
+        self.prepare_upper_sums();
+
+        match self.update_order {
+            UpdateOrder::Sequential => {
+                for i in 0..self.neuron_count {
+                    let fire = self.evaluate_neuron(i, input, clamped, output);
+                    set_bit(&mut self.values, i, fire);
+                }
+            }
+            UpdateOrder::Synchronous => {
+                let mut next = self.values.clone();
+                for i in 0..self.neuron_count {
+                    let fire = self.evaluate_neuron(i, input, clamped, output);
+                    set_bit(&mut next, i, fire);
+                }
+                self.values = next;
+            }
+            UpdateOrder::Permutation(_) | UpdateOrder::Shuffled(_) => {
+                if let UpdateOrder::Shuffled(_) = self.update_order {
+                    self.schedule.shuffle(&mut self.schedule_rng);
+                }
+                for s in 0..self.schedule.len() {
+                    let i = self.schedule[s];
+                    let fire = self.evaluate_neuron(i, input, clamped, output);
+                    // lower neurons may still be updated after this one
+                    if fire != get_bit(&self.values, i) {
+                        self.flip_upper_sums(i, fire);
+                    }
+                    set_bit(&mut self.values, i, fire);
+                }
             }
+        }
+    }
 
-            let fire = sum > v.0 as i32;
+    /// Draws the random numbers `FiringMode::Stochastic` compares against for the next update of every neuron.
+    #[inline]
+    fn draw_noise(&mut self) {
+        if let FiringMode::Stochastic(_) = self.firing_mode {
+            self.noise.resize(self.neuron_count, 0);
+            for chunk in self.noise.chunks_mut(8) {
+                for (pair, bits) in chunk.chunks_mut(2).zip(self.firing_rng.next().iter()) {
+                    pair[0] = *bits as u32;
+                    pair[1] = (*bits >> 32) as u32;
+                }
+            }
+        }
+    }
 
-            if fire {
-                self.values[i / chunk_size].0 |= 1 << (i % chunk_size);
-            } else {
-                self.values[i / chunk_size].0 &= !(1 << (i % chunk_size));
+    /// Computes the sum over the weights from neurons with a higher index for every neuron of a symmetric brain.
+    /// Those weights are spread over the triangle rows of the higher neurons, so the weights that agree with the state
+    /// of their neuron are counted a row chunk at a time, in byte sized counters that hold 8 lower neurons per word.
+    /// In index order the higher neurons are only updated after the lower ones, and a synchronous update uses
+    /// the previous state anyway, so the sums hold for a whole cycle.
+    #[inline]
+    fn prepare_upper_sums(&mut self) {
+        if !matches!(self.connectivity, Connectivity::Symmetric) {
+            return;
+        }
+
+        let chunk_size = size_of::<NeuronChunk>() * 8;
+        let mut agreeing = vec![0i32; self.neuron_count];
+        let mut counters = vec![0u64; self.neuron_count / 8];
+        for j in 1..self.neuron_count {
+            let state = get_bit(&self.values, j);
+            let offset = triangle_offset(j);
+            for c in 0..j.div_ceil(chunk_size) {
+                let weights = self.weight_matrix[offset + c].0;
+                let mut bits = if state { weights } else { !weights };
+                if c == j / chunk_size {
+                    bits &= (1 << (j % chunk_size)) - 1;
+                }
+
+                for (k, counter) in counters[c * 8..(c + 1) * 8].iter_mut().enumerate() {
+                    *counter += SPREAD_BITS[((bits >> (8 * k)) & 0xff) as usize];
+                }
             }
 
-            if i >= output_start {
-                output.push((fire, sum));
+            // empty the counters before a byte can overflow
+            if j % u8::MAX as usize == 0 || j == self.neuron_count - 1 {
+                for (i, counter) in counters.iter_mut().enumerate() {
+                    for (b, count) in agreeing[i * 8..(i + 1) * 8].iter_mut().enumerate() {
+                        *count += ((*counter >> (8 * b)) & 0xff) as i32;
+                    }
+                    *counter = 0;
+                }
             }
         }
 
+        let neuron_count = self.neuron_count;
+        self.upper_sums.clear();
+        self.upper_sums.extend(agreeing.iter().enumerate().map(|(i, &count)| 2 * count - (neuron_count - 1 - i) as i32));
+    }
+
+    /// Updates the upper sums of the neurons below `neuron` for its new state, when updating in any other order.
+    #[inline]
+    fn flip_upper_sums(&mut self, neuron: usize, state: bool) {
+        if !matches!(self.connectivity, Connectivity::Symmetric) {
+            return;
+        }
+
+        let chunk_size = size_of::<NeuronChunk>() * 8;
+        let offset = triangle_offset(neuron);
+        let row = &self.weight_matrix[offset..offset + neuron.div_ceil(chunk_size)];
+        // every weight goes from agreeing to disagreeing or back, which moves the sum by 2
+        for (sums, weights) in self.upper_sums[..neuron].chunks_mut(chunk_size).zip(row.iter()) {
+            let agreeing = if state { weights.0 } else { !weights.0 };
+            for (bit, sum) in sums.iter_mut().enumerate() {
+                *sum += 4 * ((agreeing >> bit) & 1) as i32 - 2;
+            }
+        }
+    }
+
+    /// Updates the layers of the brain in order, so the input passes through every layer exactly once
+    /// no matter the update order. The neurons of the input layer only receive the input, see `set_layers`.
+    pub fn forward(&mut self, input: &[Activation], output: &mut Vec<(bool, i32)>) -> Result<()> {
+        if input.len() != self.input_count {
+            return Err(BinaryBrainError::WrongInputShape);
+        }
+        if self.layers.is_none() {
+            return Err(BinaryBrainError::InvalidLayers);
+        }
+
+        output.clear();
+        output.resize(self.output_count, (false, 0));
+        self.draw_noise();
+
+        // neurons only depend on the previous layer, so updating in index order finishes every layer before the next one
+        for i in 0..self.neuron_count {
+            let fire = self.evaluate_neuron(i, input, None, output);
+            set_bit(&mut self.values, i, fire);
+        }
+
         Ok(())
     }
 
+    /// Cycles the brain with the same input until the neuron state stops changing,
+    /// a previously seen state repeats or `max_cycles` cycles have been run.  
+    /// `output` holds the outputs of the last cycle that was run.  
+    /// With `UpdateOrder::Shuffled` or `FiringMode::Stochastic` a repeated state does not imply a limit cycle,
+    /// so only fixed points are detected. For stochastic brains these are only a snapshot of a state that was kept for one cycle.
+    pub fn cycle_until_stable(&mut self, input: &[Activation], output: &mut Vec<(bool, i32)>, max_cycles: usize) -> Result<Settle> {
+        let deterministic = self.firing_mode == FiringMode::Threshold
+            && !matches!(self.update_order, UpdateOrder::Shuffled(_));
+        let mut seen = HashMap::new();
+        seen.insert(self.state_key(), 0);
+
+        for cycles in 1..=max_cycles {
+            self.cycle(input, output)?;
+
+            if !deterministic {
+                seen.retain(|_, &mut seen_at| seen_at == cycles - 1);
+            }
+
+            if let Some(previous) = seen.insert(self.state_key(), cycles) {
+                let convergence = match cycles - previous {
+                    1 => Convergence::FixedPoint,
+                    period => Convergence::LimitCycle(period),
+                };
+
+                return Ok(Settle {
+                    cycles,
+                    convergence,
+                });
+            }
+        }
+
+        Ok(Settle {
+            cycles: max_cycles,
+            convergence: Convergence::Unsettled,
+        })
+    }
+
+    /// Everything that determines the next cycle apart from the parameters and the input.
+    fn state_key(&self) -> Vec<u64> {
+        let mut key: Vec<u64> = self.values.iter().map(|chunk| chunk.0).collect();
+
+        if let NeuronModel::LeakyIntegrate { .. } = self.neuron_model {
+            key.extend(self.potential.iter().map(|p| p.to_bits() as u64));
+            key.extend(self.refractory.iter().map(|&r| r as u64));
+        }
+
+        key
+    }
+
+    /// Calculates whether `neuron` fires given the current state.  
+    /// Only the membrane potential of the neuron is updated, its state is left to the caller.
+    #[inline]
+    fn evaluate_neuron(&mut self, neuron: usize, input: &[Activation], clamped: Option<&[NeuronChunk]>, output: &mut [(bool, i32)]) -> bool {
+        let mut sum = self.calc_sum(neuron);
+        
+        if neuron < input.len() {
+            sum += input[neuron].0 as i32;
+        }
+
+        let fire = match clamped {
+            Some(clamped) if get_bit(clamped, neuron) => get_bit(&self.values, neuron),
+            _ => match self.neuron_model {
+                NeuronModel::Binary => self.fires(neuron, sum as f32),
+                NeuronModel::LeakyIntegrate { leak, refractory } => {
+                    // report the potential since that is what gets compared to the activation
+                    if self.refractory[neuron] > 0 {
+                        self.refractory[neuron] -= 1;
+                        sum = self.potential[neuron].round() as i32;
+                        false
+                    } else {
+                        let potential = self.potential[neuron] * (1.0 - leak) + sum as f32;
+                        sum = potential.round() as i32;
+
+                        if self.fires(neuron, potential) {
+                            self.potential[neuron] = 0.0;
+                            self.refractory[neuron] = refractory;
+                            true
+                        } else {
+                            self.potential[neuron] = potential;
+                            false
+                        }
+                    }
+                }
+            },
+        };
+
+        let output_start = self.neuron_count - self.output_count;
+        if neuron >= output_start {
+            output[neuron - output_start] = (fire, sum);
+        }
+
+        fire
+    }
+
+    #[inline]
+    fn fires(&self, neuron: usize, drive: f32) -> bool {
+        let threshold = match &self.off_act {
+            Some(off_act) if get_bit(&self.values, neuron) => off_act[neuron].0 as f32,
+            _ => self.act[neuron].0 as f32,
+        };
+
+        match self.firing_mode {
+            FiringMode::Threshold => drive > threshold,
+            FiringMode::Stochastic(temperature) => {
+                let x = (drive - threshold) as f64 / temperature as f64;
+                let p = 1.0 / (1.0 + (-x).exp());
+                (self.noise[neuron] as f64) < p * (u32::MAX as f64 + 1.0)
+            }
+        }
+    }
+
     #[inline]
     fn calc_sum(&self, neuron: usize) -> i32 {
+        match self.connectivity {
+            Connectivity::Dense if !self.layer_sources.is_empty() => {
+                let (start, end) = self.layer_sources[neuron];
+                self.calc_masked_sum(neuron, start, end)
+            }
+            Connectivity::Dense => self.calc_dense_sum(neuron),
+            Connectivity::Symmetric => {
+                self.calc_lower_sum(neuron) + self.upper_sums[neuron]
+            }
+            Connectivity::Grid { width, radius } => self.calc_grid_sum(neuron, width, radius, neuron),
+            Connectivity::SharedGrid { width, radius, ref groups } => self.calc_grid_sum(neuron, width, radius, groups[neuron]),
+        }
+    }
+
+    #[inline]
+    fn calc_dense_sum(&self, neuron: usize) -> i32 {
         let mut sum = 0;
         let row_size = self.values.len();
 
@@ -183,16 +734,201 @@ impl BinaryBrain {
         sum
     }
 
+    /// Sum over the weights from the neurons `start..end` in the dense row of `neuron`.
+    #[inline]
+    fn calc_masked_sum(&self, neuron: usize, start: usize, end: usize) -> i32 {
+        let chunk_size = size_of::<NeuronChunk>() * 8;
+        let row = &self.weight_matrix[neuron * self.values.len()..(neuron + 1) * self.values.len()];
+        let mut sum = 0;
+        if start == end {
+            return 0;
+        }
+
+        let chunks = row.iter().zip(self.values.iter()).enumerate();
+        for (chunk, (weights, values)) in chunks.take(end.div_ceil(chunk_size)).skip(start / chunk_size) {
+            let from = start.max(chunk * chunk_size) - chunk * chunk_size;
+            let to = end.min((chunk + 1) * chunk_size) - chunk * chunk_size;
+            let used = (!0u64 >> (chunk_size - (to - from))) << from;
+            sum += weighted_sum(weights.0, values.0, used);
+        }
+
+        sum
+    }
+
+    /// Sum over the weights in the stored triangle row of `neuron`, so from neurons with a lower index.
+    #[inline]
+    fn calc_lower_sum(&self, neuron: usize) -> i32 {
+        let chunk_size = size_of::<NeuronChunk>() * 8;
+        let offset = triangle_offset(neuron);
+        let row_size = neuron.div_ceil(chunk_size);
+        let mut sum = 0;
+
+        let weight_iter = self.weight_matrix[offset..offset + row_size].iter();
+        for (i, (weights, values)) in weight_iter.zip(self.values.iter()).enumerate() {
+            let used = if i == neuron / chunk_size { (1 << (neuron % chunk_size)) - 1 } else { !0 };
+            sum += weighted_sum(weights.0, values.0, used);
+        }
+
+        sum
+    }
+
+    /// Sum over the square neighbourhood of `neuron` on the grid, weighted by the weights in row `row_index`.
+    /// The states of the neighbours are gathered into chunks so they can be weighted a chunk at a time.
+    #[inline]
+    fn calc_grid_sum(&self, neuron: usize, width: usize, radius: usize, row_index: usize) -> i32 {
+        let chunk_size = size_of::<NeuronChunk>() * 8;
+        let height = self.neuron_count / width;
+        let side = 2 * radius + 1;
+        let row_size = grid_row_size(radius);
+        let row = &self.weight_matrix[row_index * row_size..(row_index + 1) * row_size];
+        let (x, y) = (neuron % width, neuron / width);
+
+        let mut sum = 0;
+        let mut states = 0;
+        let mut bit = 0;
+        let mut chunk = 0;
+        for dy in 0..side {
+            let ny = (y + height + dy - radius) % height;
+            for dx in 0..side {
+                let nx = (x + width + dx - radius) % width;
+                if get_bit(&self.values, ny * width + nx) {
+                    states |= 1 << bit;
+                }
+
+                bit += 1;
+                if bit == chunk_size {
+                    sum += weighted_sum(row[chunk].0, states, !0);
+                    states = 0;
+                    bit = 0;
+                    chunk += 1;
+                }
+            }
+        }
+        if bit != 0 {
+            sum += weighted_sum(row[chunk].0, states, (1 << bit) - 1);
+        }
+
+        sum
+    }
+
+    /// The Hopfield energy of the current state, which never increases when a single neuron is updated
+    /// with `FiringMode::Threshold` and `NeuronModel::Binary`, so sequential, shuffled and permuted cycles always converge.  
+    /// `input` is the input bias that is used for cycling, or empty when cycling with `cycle_clamped`.
+    /// Only brains with `Connectivity::Symmetric` have an energy.
+    pub fn energy(&self, input: &[Activation]) -> Result<i64> {
+        if self.connectivity != Connectivity::Symmetric {
+            return Err(BinaryBrainError::UnsupportedConnectivity);
+        }
+        if !input.is_empty() && input.len() != self.input_count {
+            return Err(BinaryBrainError::WrongInputShape);
+        }
+
+        let mut energy = 0;
+        for i in 0..self.neuron_count {
+            let state = if get_bit(&self.values, i) { 1 } else { -1 };
+            let mut field = -(self.act[i].0 as i64);
+            if i < input.len() {
+                field += input[i].0 as i64;
+            }
+            // every pair of neurons is counted once through the lower triangle
+            energy -= state * (self.calc_lower_sum(i) as i64 + field);
+        }
+
+        Ok(energy)
+    }
+
     #[inline]
     pub fn weights(&self) -> &[NeuronChunk] {
         self.weight_matrix.as_slice()
     }
 
+    #[inline]
+    pub fn connectivity(&self) -> &Connectivity {
+        &self.connectivity
+    }
+
     #[inline]
     pub fn activations(&self) -> &[Activation] {
         self.act.as_slice()
     }
 
+    /// The activations that firing neurons have to stay above to keep firing, if the brain has them.
+    #[inline]
+    pub fn off_activations(&self) -> Option<&[Activation]> {
+        self.off_act.as_deref()
+    }
+
+    /// Gives every neuron a separate activation that its sum has to stay above to keep firing once it fired.
+    /// An off activation below the regular activation makes the neuron latch, an equal one behaves like a regular neuron,
+    /// and one above the regular activation is not allowed.  
+    /// Passing `None` turns every neuron back into a regular neuron.
+    pub fn set_off_activations(&mut self, off_activations: Option<Vec<Activation>>) -> Result<()> {
+        if let Some(off_act) = &off_activations {
+            check_off_activations(off_act, &self.act)?;
+        }
+
+        self.off_act = off_activations;
+        Ok(())
+    }
+
+    #[inline]
+    pub fn neuron_type(&self, neuron: usize) -> NeuronType {
+        match (get_bit(&self.type_mask, neuron), get_bit(&self.type_sign, neuron)) {
+            (false, _) => NeuronType::Free,
+            (true, true) => NeuronType::Excitatory,
+            (true, false) => NeuronType::Inhibitory,
+        }
+    }
+
+    /// Sets the type of every neuron and overwrites the outgoing weights of excitatory and inhibitory neurons to match.  
+    /// `types` needs one entry per neuron. Only brains with `Connectivity::Dense` support neuron types.
+    pub fn set_neuron_types(&mut self, types: &[NeuronType]) -> Result<()> {
+        if self.connectivity != Connectivity::Dense {
+            return Err(BinaryBrainError::UnsupportedConnectivity);
+        }
+        if types.len() != self.neuron_count {
+            return Err(BinaryBrainError::WrongNeuronTypesShape);
+        }
+
+        for (i, neuron_type) in types.iter().enumerate() {
+            set_bit(&mut self.type_mask, i, *neuron_type != NeuronType::Free);
+            set_bit(&mut self.type_sign, i, *neuron_type == NeuronType::Excitatory);
+        }
+        self.enforce_neuron_types();
+
+        Ok(())
+    }
+
+    fn enforce_neuron_types(&mut self) {
+        if self.type_mask.iter().all(|chunk| chunk.0 == 0) {
+            return;
+        }
+
+        for row in self.weight_matrix.chunks_mut(self.type_mask.len()) {
+            for (weights, (mask, sign)) in row.iter_mut().zip(self.type_mask.iter().zip(self.type_sign.iter())) {
+                weights.0 = (weights.0 & !mask.0) | (sign.0 & mask.0);
+            }
+        }
+    }
+
+    /// Whether each neuron fired in the last cycle.
+    pub fn state(&self) -> Vec<bool> {
+        (0..self.neuron_count).map(|i| get_bit(&self.values, i)).collect()
+    }
+
+    /// Overwrites whether each neuron fired in the last cycle, which is what the next cycle starts from.
+    pub fn set_state(&mut self, state: &[bool]) -> Result<()> {
+        if state.len() != self.neuron_count {
+            return Err(BinaryBrainError::WrongStateShape);
+        }
+
+        for (i, &fired) in state.iter().enumerate() {
+            set_bit(&mut self.values, i, fired);
+        }
+
+        Ok(())
+    }
+
     #[inline]
     pub fn input_count(&self) -> usize {
         self.input_count
@@ -202,16 +938,474 @@ impl BinaryBrain {
     pub fn output_count(&self) -> usize {
         self.output_count
     }
+
+    #[inline]
+    pub fn layers(&self) -> Option<&[usize]> {
+        self.layers.as_deref()
+    }
+
+    /// Divides the neurons into non-empty layers given by their amount of neurons in index order,
+    /// where the first layer holds exactly the inputs and the last layer exactly the outputs,
+    /// so a brain needs inputs and outputs to have layers.
+    /// A neuron then only receives weights from the previous layer, the neurons of the input layer only receive the input.
+    /// The masked weights are kept, so they are back when the layers are removed with `None`.
+    /// Resizing a brain removes its layers, while pruning, merging duplicates and canonicalizing don't support them.
+    /// Only brains with `Connectivity::Dense` support layers.
+    pub fn set_layers(&mut self, layers: Option<Vec<usize>>) -> Result<()> {
+        let layers = match layers {
+            Some(layers) => layers,
+            None => {
+                self.layers = None;
+                self.layer_sources.clear();
+                return Ok(());
+            }
+        };
+        if self.connectivity != Connectivity::Dense {
+            return Err(BinaryBrainError::UnsupportedConnectivity);
+        }
+        validate_layers(&layers, self.input_count, self.output_count, self.neuron_count)?;
+
+        self.layer_sources.clear();
+        let mut previous = (0, 0);
+        for &size in layers.iter() {
+            let start = previous.1;
+            self.layer_sources.extend(std::iter::repeat_n(previous, size));
+            previous = (start, start + size);
+        }
+        self.layers = Some(layers);
+
+        Ok(())
+    }
+
+    #[inline]
+    pub fn update_order(&self) -> &UpdateOrder {
+        &self.update_order
+    }
+
+    /// Sets the order in which `cycle` updates the neurons.  
+    /// This also restarts the sequence of orders generated by `UpdateOrder::Shuffled`.
+    pub fn set_update_order(&mut self, order: UpdateOrder) -> Result<()> {
+        match &order {
+            UpdateOrder::Permutation(permutation) => {
+                let mut seen = vec![false; self.neuron_count];
+                if permutation.len() != self.neuron_count {
+                    return Err(BinaryBrainError::InvalidUpdateOrder);
+                }
+                for &i in permutation.iter() {
+                    if i >= self.neuron_count || seen[i] {
+                        return Err(BinaryBrainError::InvalidUpdateOrder);
+                    }
+                    seen[i] = true;
+                }
+                self.schedule = permutation.clone();
+            }
+            UpdateOrder::Shuffled(seed) => {
+                self.schedule = (0..self.neuron_count).collect();
+                self.schedule_rng = StdRng::seed_from_u64(*seed);
+            }
+            UpdateOrder::Sequential | UpdateOrder::Synchronous => {
+                self.schedule = (0..self.neuron_count).collect();
+            }
+        }
+
+        self.update_order = order;
+        Ok(())
+    }
+
+    #[inline]
+    pub fn firing_mode(&self) -> FiringMode {
+        self.firing_mode
+    }
+
+    /// Sets how neurons decide to fire, the temperature of `FiringMode::Stochastic` has to be positive and finite.  
+    /// Lowering the temperature over time anneals the brain towards the behaviour of `FiringMode::Threshold`.
+    pub fn set_firing_mode(&mut self, mode: FiringMode) -> Result<()> {
+        if let FiringMode::Stochastic(temperature) = mode {
+            if !(temperature > 0.0 && temperature.is_finite()) {
+                return Err(BinaryBrainError::InvalidTemperature);
+            }
+        }
+
+        self.firing_mode = mode;
+        Ok(())
+    }
+
+    /// Restarts the random number generator of `FiringMode::Stochastic` from `seed`,
+    /// so cycling from the same state with the same inputs fires the same neurons again.
+    pub fn set_firing_seed(&mut self, seed: u64) {
+        self.firing_rng = Xoshiro128PlusPlusAvx2::from_rng(&mut StdRng::seed_from_u64(seed));
+    }
+
+    #[inline]
+    pub fn neuron_model(&self) -> NeuronModel {
+        self.neuron_model
+    }
+
+    /// Sets the neuron model and clears all membrane potentials and refractory periods.  
+    /// The leak of `NeuronModel::LeakyIntegrate` has to be in the range [0, 1].
+    pub fn set_neuron_model(&mut self, model: NeuronModel) -> Result<()> {
+        if let NeuronModel::LeakyIntegrate { leak, .. } = model {
+            if !(0.0..=1.0).contains(&leak) {
+                return Err(BinaryBrainError::InvalidNeuronModel);
+            }
+        }
+
+        self.potential.iter_mut().for_each(|p| *p = 0.0);
+        self.refractory.iter_mut().for_each(|r| *r = 0);
+        self.neuron_model = model;
+        Ok(())
+    }
+}
+
+/// The amount of chunks a row of weights over the neighbourhood of a grid neuron takes up.
+#[inline]
+fn grid_row_size(radius: usize) -> usize {
+    ((2 * radius + 1) * (2 * radius + 1)).div_ceil(size_of::<NeuronChunk>() * 8)
+}
+
+/// Sum of the +1/-1 products of the weights and states where `used` has a bit set.
+#[inline]
+fn weighted_sum(weights: u64, values: u64, used: u64) -> i32 {
+    let popcount = (!(weights ^ values) & used).count_ones() as i32;
+    2 * popcount - used.count_ones() as i32
+}
+
+/// Spreads the 8 bits of a byte over the 8 bytes of a word, so bits can be counted with one addition per byte.
+static SPREAD_BITS: [u64; 256] = spread_bits();
+
+const fn spread_bits() -> [u64; 256] {
+    let mut table = [0; 256];
+    let mut byte = 0;
+    while byte < 256 {
+        let mut bit = 0;
+        while bit < 8 {
+            if byte & (1 << bit) != 0 {
+                table[byte] |= 1 << (8 * bit);
+            }
+            bit += 1;
+        }
+        byte += 1;
+    }
+    table
+}
+
+/// Offset of the row of `neuron` in the lower triangle of a symmetric weight matrix.  
+/// Row `i` holds the weights from neurons `0..i` and takes up `ceil(i / 64)` chunks.
+#[inline]
+fn triangle_offset(neuron: usize) -> usize {
+    let chunk_size = size_of::<NeuronChunk>() * 8;
+    if neuron == 0 {
+        return 0;
+    }
+
+    let full = (neuron - 1) / chunk_size;
+    let rest = (neuron - 1) % chunk_size;
+    chunk_size * full * (full + 1) / 2 + (full + 1) * rest
+}
+
+#[inline]
+fn get_bit(chunks: &[NeuronChunk], index: usize) -> bool {
+    let chunk_size = size_of::<NeuronChunk>() * 8;
+
+    chunks[index / chunk_size].0 & (1 << (index % chunk_size)) != 0
+}
+
+#[inline]
+fn set_bit(chunks: &mut [NeuronChunk], index: usize, bit: bool) {
+    let chunk_size = size_of::<NeuronChunk>() * 8;
+
+    if bit {
+        chunks[index / chunk_size].0 |= 1 << (index % chunk_size);
+    } else {
+        chunks[index / chunk_size].0 &= !(1 << (index % chunk_size));
+    }
 }
 
 #[repr(transparent)]
-#[derive(Debug, Copy, Clone, Default)]
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
 pub struct NeuronChunk(pub u64);
 
 #[repr(transparent)]
-#[derive(Debug, Copy, Clone, Default)]
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
 pub struct Activation(pub i8);
 
+/// The order in which `BinaryBrain::cycle` updates its neurons.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum UpdateOrder {
+    /// Neurons are updated one at a time in index order, so inputs first and outputs last.
+    #[default]
+    Sequential,
+    /// Neurons are updated one at a time in the given order, which has to contain every neuron index exactly once.
+    Permutation(Vec<usize>),
+    /// Neurons are updated one at a time in a random order that changes every cycle.
+    /// The sequence of orders is determined by the seed.
+    Shuffled(u64),
+    /// All neurons are updated at once, using only the state of the previous cycle.
+    Synchronous,
+}
+
+impl UpdateOrder {
+    fn read_from<R: io::Read>(reader: &mut R) -> io::Result<UpdateOrder> {
+        match reader.read_u8()? {
+            0 => Ok(UpdateOrder::Sequential),
+            1 => {
+                let len = reader.read_u64::<LittleEndian>()? as usize;
+                let mut permutation = Vec::with_capacity(len);
+                for _ in 0..len {
+                    permutation.push(reader.read_u64::<LittleEndian>()? as usize);
+                }
+                Ok(UpdateOrder::Permutation(permutation))
+            }
+            2 => Ok(UpdateOrder::Shuffled(reader.read_u64::<LittleEndian>()?)),
+            3 => Ok(UpdateOrder::Synchronous),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidData, "unknown update order")),
+        }
+    }
+
+    fn write_to<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        match self {
+            UpdateOrder::Sequential => writer.write_u8(0),
+            UpdateOrder::Permutation(permutation) => {
+                writer.write_u8(1)?;
+                writer.write_u64::<LittleEndian>(permutation.len() as u64)?;
+                for &i in permutation.iter() {
+                    writer.write_u64::<LittleEndian>(i as u64)?;
+                }
+                Ok(())
+            }
+            UpdateOrder::Shuffled(seed) => {
+                writer.write_u8(2)?;
+                writer.write_u64::<LittleEndian>(*seed)
+            }
+            UpdateOrder::Synchronous => writer.write_u8(3),
+        }
+    }
+}
+
+/// How a neuron decides to fire given its sum.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum FiringMode {
+    /// A neuron fires when its sum is above its activation.
+    #[default]
+    Threshold,
+    /// A neuron fires with probability `1 / (1 + e^(-(sum - activation) / temperature))`,
+    /// making it a unit of a Boltzmann machine.
+    Stochastic(f32),
+}
+
+impl FiringMode {
+    fn read_from<R: io::Read>(reader: &mut R) -> io::Result<FiringMode> {
+        match reader.read_u8()? {
+            0 => Ok(FiringMode::Threshold),
+            1 => Ok(FiringMode::Stochastic(reader.read_f32::<LittleEndian>()?)),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidData, "unknown firing mode")),
+        }
+    }
+
+    fn write_to<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        match self {
+            FiringMode::Threshold => writer.write_u8(0),
+            FiringMode::Stochastic(temperature) => {
+                writer.write_u8(1)?;
+                writer.write_f32::<LittleEndian>(*temperature)
+            }
+        }
+    }
+}
+
+/// Which neurons are connected and how the weights are laid out in the weight matrix.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Connectivity {
+    /// Every neuron is connected to every neuron, including itself.
+    /// The weight matrix holds a row of `total_count` weights per neuron, bit `j` of row `i` being the weight from neuron `j` to neuron `i`.
+    #[default]
+    Dense,
+    /// Every neuron is connected to every other neuron with the same weight in both directions, like in a Hopfield network.
+    /// The weight matrix only holds the lower triangle, row `i` containing the weights from neurons `0..i` padded to whole chunks.
+    Symmetric,
+    /// Neurons are laid out on a grid of `width` columns in row major order, and every neuron is only connected
+    /// to the square of neurons within `radius` steps of it, including itself. The grid wraps around at the edges.
+    /// The weight matrix holds a row of `(2 * radius + 1)^2` weights per neuron padded to whole chunks,
+    /// ordered from the top left to the bottom right of the square.
+    Grid {
+        width: usize,
+        radius: usize,
+    },
+    /// Like `Grid`, but neurons are divided into groups that share one row of weights, like the kernel of a convolution.
+    /// `groups` holds the group of every neuron, which has to be below the amount of neurons,
+    /// and the weight matrix holds a row per group up to the highest group,
+    /// so the weights are translated over the grid along with the neurons of a group.
+    SharedGrid {
+        width: usize,
+        radius: usize,
+        groups: Vec<usize>,
+    },
+}
+
+impl Connectivity {
+    /// The amount of chunks the weight matrix of a brain with `total_count` neurons takes up.  
+    /// This is always a multiple of 4 so trainers can process 256 weights at a time.
+    pub fn weight_chunk_count(&self, total_count: usize) -> usize {
+        match self {
+            Connectivity::Dense => (total_count * total_count) / (size_of::<NeuronChunk>() * 8),
+            Connectivity::Symmetric => triangle_offset(total_count).next_multiple_of(4),
+            Connectivity::Grid { radius, .. } | Connectivity::SharedGrid { radius, .. } => {
+                (self.grid_row_count(total_count) * grid_row_size(*radius)).next_multiple_of(4)
+            }
+        }
+    }
+
+    /// The amount of weight rows of a grid connectivity.
+    fn grid_row_count(&self, total_count: usize) -> usize {
+        match self {
+            Connectivity::SharedGrid { groups, .. } => groups.iter().max().map_or(0, |group| group + 1),
+            _ => total_count,
+        }
+    }
+
+    /// Checks whether a brain with `total_count` neurons can have this connectivity.
+    pub fn validate(&self, total_count: usize) -> Result<()> {
+        if let Connectivity::Grid { width, radius } | Connectivity::SharedGrid { width, radius, .. } = *self {
+            // the neighbourhood can't wrap around onto itself, `2 * radius + 1 <= size` written so a huge radius can't overflow
+            let fits = |size: usize| size > 0 && radius <= (size - 1) / 2;
+            if width == 0 || !total_count.is_multiple_of(width) || !fits(width) || !fits(total_count / width) {
+                return Err(BinaryBrainError::InvalidConnectivity);
+            }
+        }
+        if let Connectivity::SharedGrid { groups, .. } = self {
+            // there can't be more groups than neurons, which also bounds the amount of weight rows
+            if groups.len() != total_count || groups.iter().any(|&group| group >= total_count) {
+                return Err(BinaryBrainError::InvalidConnectivity);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read_from<R: io::Read>(reader: &mut R) -> io::Result<Connectivity> {
+        match reader.read_u8()? {
+            0 => Ok(Connectivity::Dense),
+            1 => Ok(Connectivity::Symmetric),
+            2 => Ok(Connectivity::Grid {
+                width: reader.read_u64::<LittleEndian>()? as usize,
+                radius: reader.read_u64::<LittleEndian>()? as usize,
+            }),
+            3 => {
+                let width = reader.read_u64::<LittleEndian>()? as usize;
+                let radius = reader.read_u64::<LittleEndian>()? as usize;
+                let len = reader.read_u64::<LittleEndian>()? as usize;
+                // the length isn't validated yet, so it can't decide how much memory to reserve
+                let mut groups = Vec::new();
+                for _ in 0..len {
+                    groups.push(reader.read_u64::<LittleEndian>()? as usize);
+                }
+                Ok(Connectivity::SharedGrid { width, radius, groups })
+            }
+            _ => Err(io::Error::new(io::ErrorKind::InvalidData, "unknown connectivity")),
+        }
+    }
+
+    fn write_to<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        match self {
+            Connectivity::Dense => writer.write_u8(0),
+            Connectivity::Symmetric => writer.write_u8(1),
+            Connectivity::Grid { width, radius } => {
+                writer.write_u8(2)?;
+                writer.write_u64::<LittleEndian>(*width as u64)?;
+                writer.write_u64::<LittleEndian>(*radius as u64)
+            }
+            Connectivity::SharedGrid { width, radius, groups } => {
+                writer.write_u8(3)?;
+                writer.write_u64::<LittleEndian>(*width as u64)?;
+                writer.write_u64::<LittleEndian>(*radius as u64)?;
+                writer.write_u64::<LittleEndian>(groups.len() as u64)?;
+                for &group in groups.iter() {
+                    writer.write_u64::<LittleEndian>(group as u64)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Constrains the outgoing weights of a neuron, like Dale's law does for biological neurons.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum NeuronType {
+    /// Outgoing weights can be both +1 and -1.
+    #[default]
+    Free,
+    /// All outgoing weights are +1.
+    Excitatory,
+    /// All outgoing weights are -1.
+    Inhibitory,
+}
+
+/// What a neuron remembers between cycles.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum NeuronModel {
+    /// A neuron only remembers whether it fired, its sum is calculated from scratch every cycle.
+    #[default]
+    Binary,
+    /// A neuron accumulates its sums in a membrane potential that loses a `leak` fraction every cycle
+    /// and fires when the potential is above its activation.
+    /// After firing the potential is reset and the neuron stays silent for `refractory` cycles.
+    LeakyIntegrate {
+        leak: f32,
+        refractory: u32,
+    },
+}
+
+impl NeuronModel {
+    fn read_from<R: io::Read>(reader: &mut R) -> io::Result<NeuronModel> {
+        match reader.read_u8()? {
+            0 => Ok(NeuronModel::Binary),
+            1 => Ok(NeuronModel::LeakyIntegrate {
+                leak: reader.read_f32::<LittleEndian>()?,
+                refractory: reader.read_u32::<LittleEndian>()?,
+            }),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidData, "unknown neuron model")),
+        }
+    }
+
+    fn write_to<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        match self {
+            NeuronModel::Binary => writer.write_u8(0),
+            NeuronModel::LeakyIntegrate { leak, refractory } => {
+                writer.write_u8(1)?;
+                writer.write_f32::<LittleEndian>(*leak)?;
+                writer.write_u32::<LittleEndian>(*refractory)
+            }
+        }
+    }
+}
+
+/// Result of `BinaryBrain::cycle_until_stable`.
+/// Checks that there is an off activation for every activation and that none is above its activation.
+pub(crate) fn check_off_activations(off_act: &[Activation], act: &[Activation]) -> Result<()> {
+    if off_act.len() != act.len() || off_act.iter().zip(act.iter()).any(|(off, act)| off.0 > act.0) {
+        return Err(BinaryBrainError::InvalidOffActivations);
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Settle {
+    /// The amount of cycles that were run.
+    pub cycles: usize,
+    pub convergence: Convergence,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Convergence {
+    /// The last cycle did not change the state of any neuron.
+    FixedPoint,
+    /// The state repeats itself every `period` cycles.
+    LimitCycle(usize),
+    /// No repeating state was found within the cycle limit.
+    Unsettled,
+}
+
 #[derive(Debug)]
 pub enum BinaryBrainError {
     TotalNotDivisbleByChunkSize,
@@ -220,6 +1414,25 @@ pub enum BinaryBrainError {
     InvalidPopSize,
     ZeroTournamentSize,
     InvalidWeightActivationCombo,
+    InvalidUpdateOrder,
+    InvalidNeuronIndex,
+    InvalidTemperature,
+    InvalidNeuronModel,
+    UnsupportedConnectivity,
+    InvalidConnectivity,
+    WrongStateShape,
+    UnsupportedDynamics,
+    InvalidModuleIndex,
+    MismatchedBrains,
+    InvalidInit,
+    InvalidLayers,
+    InvalidEncoding,
+    InvalidDecoding,
+    WrongOutputShape,
+    InvalidReadout,
+    InvalidOffActivations,
+    WrongNeuronTypesShape,
+    OddCancellingCount,
 }
 
 
@@ -250,6 +1463,17 @@ mod benches {
         });
     } 
 
+    #[bench]
+    fn cycle_clamped_512(b: &mut Bencher) {
+        let mut nn = BinaryBrain::new(32, 32, 512).unwrap();
+        let mut output = vec![];
+        let clamps: Vec<_> = (0..32).map(|i| (i, i % 2 == 0)).collect();
+
+        b.iter(|| {
+            nn.cycle_clamped(black_box(&clamps), &mut output).unwrap();
+        });
+    }
+
     #[bench]
     fn cycle_4096(b: &mut Bencher) {
         let mut nn = BinaryBrain::new(32, 32, 4096).unwrap();
@@ -261,6 +1485,29 @@ mod benches {
         });
     }
 
+    #[bench]
+    fn cycle_symmetric_4096(b: &mut Bencher) {
+        let mut nn = BinaryBrain::new_with_connectivity(32, 32, 4096, Connectivity::Symmetric).unwrap();
+        let mut output = vec![];
+
+        b.iter(|| {
+            let input = black_box(&[Activation(0); 32]);
+            nn.cycle(input, &mut output).unwrap();
+        });
+    }
+
+    #[bench]
+    fn cycle_grid_4096(b: &mut Bencher) {
+        let connectivity = Connectivity::Grid { width: 64, radius: 2 };
+        let mut nn = BinaryBrain::new_with_connectivity(32, 32, 4096, connectivity).unwrap();
+        let mut output = vec![];
+
+        b.iter(|| {
+            let input = black_box(&[Activation(0); 32]);
+            nn.cycle(input, &mut output).unwrap();
+        });
+    }
+
     #[bench]
     fn cycle_32768(b: &mut Bencher) {
         let mut nn = BinaryBrain::new(32, 32, 32768).unwrap();
@@ -0,0 +1,324 @@
+use crate::*;
+use std::io::Write;
+
+/// The difference between a brain and a base brain, which turns the base brain into the other brain when applied.
+/// Weights are stored as the positions of the flipped bits and activations and neuron types as their new value,
+/// the configuration and state are not part of a patch.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Patch {
+    neuron_count: usize,
+    connectivity: Connectivity,
+    /// Sorted bit positions in the weight matrix.
+    flips: Vec<usize>,
+    activations: Vec<(usize, Activation)>,
+    /// `None` when the patched brain has no off activations,
+    /// otherwise the changes against the off activations of the base or its activations when it has none.
+    off_activations: Option<Vec<(usize, Activation)>>,
+    neuron_types: Vec<(usize, NeuronType)>,
+}
+
+impl Patch {
+    /// Creates a patch that turns `base` into `target`, which need the same connectivity and amount of neurons, inputs and outputs.
+    pub fn new(base: &BinaryBrain, target: &BinaryBrain) -> Result<Patch> {
+        if base.connectivity != target.connectivity
+            || base.neuron_count != target.neuron_count
+            || base.input_count != target.input_count
+            || base.output_count != target.output_count {
+            return Err(BinaryBrainError::MismatchedBrains);
+        }
+
+        let chunk_size = size_of::<NeuronChunk>() * 8;
+        let mut flips = Vec::new();
+        for (i, (a, b)) in base.weight_matrix.iter().zip(target.weight_matrix.iter()).enumerate() {
+            let mut changed = a.0 ^ b.0;
+            while changed != 0 {
+                flips.push(i * chunk_size + changed.trailing_zeros() as usize);
+                changed &= changed - 1;
+            }
+        }
+
+        let changes = |a: &[Activation], b: &[Activation]| -> Vec<(usize, Activation)> {
+            (0..a.len()).filter(|&i| a[i] != b[i]).map(|i| (i, b[i])).collect()
+        };
+        let off_activations = target.off_act.as_ref()
+            .map(|off_act| changes(base.off_act.as_ref().unwrap_or(&base.act), off_act));
+
+        Ok(Patch {
+            neuron_count: base.neuron_count,
+            connectivity: base.connectivity.clone(),
+            flips,
+            activations: changes(&base.act, &target.act),
+            off_activations,
+            neuron_types: (0..base.neuron_count)
+                .filter(|&i| base.neuron_type(i) != target.neuron_type(i))
+                .map(|i| (i, target.neuron_type(i)))
+                .collect(),
+        })
+    }
+
+    /// Applies the patch to a brain with the connectivity and amount of neurons of the base it was created from.
+    /// When the patched brain would have an off activation above its activation the brain is left unchanged.
+    pub fn apply(&self, brain: &mut BinaryBrain) -> Result<()> {
+        if brain.neuron_count != self.neuron_count || brain.connectivity != self.connectivity {
+            return Err(BinaryBrainError::MismatchedBrains);
+        }
+
+        // off activation changes are against the activations of the base, so take them before they change
+        let off_act = match &self.off_activations {
+            Some(changes) => {
+                let mut off_act = brain.off_act.clone().unwrap_or_else(|| brain.act.clone());
+                for &(i, activation) in changes.iter() {
+                    off_act[i] = activation;
+                }
+                Some(off_act)
+            }
+            None => None,
+        };
+        let mut act = brain.act.clone();
+        for &(i, activation) in self.activations.iter() {
+            act[i] = activation;
+        }
+        if let Some(off_act) = &off_act {
+            check_off_activations(off_act, &act)?;
+        }
+
+        for &position in self.flips.iter() {
+            let weight = get_bit(&brain.weight_matrix, position);
+            set_bit(&mut brain.weight_matrix, position, !weight);
+        }
+        brain.act = act;
+        brain.off_act = off_act;
+
+        for &(i, neuron_type) in self.neuron_types.iter() {
+            set_bit(&mut brain.type_mask, i, neuron_type != NeuronType::Free);
+            set_bit(&mut brain.type_sign, i, neuron_type == NeuronType::Excitatory);
+        }
+        brain.enforce_neuron_types();
+
+        Ok(())
+    }
+
+    /// The amount of weights the patch flips.
+    pub fn flipped_weights(&self) -> usize {
+        self.flips.len()
+    }
+
+    /// Whether applying the patch changes nothing.
+    pub fn is_empty(&self) -> bool {
+        self.flips.is_empty()
+            && self.activations.is_empty()
+            && self.off_activations.as_ref().is_none_or(|changes| changes.is_empty())
+            && self.neuron_types.is_empty()
+    }
+
+    /// Reads a patch written by `write_to_file`.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Patch> {
+        let mut file = io::BufReader::new(fs::File::open(path)?);
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "invalid patch");
+
+        let neuron_count = file.read_u64::<LittleEndian>()? as usize;
+        let connectivity = Connectivity::read_from(&mut file)?;
+        connectivity.validate(neuron_count).map_err(|_| invalid())?;
+
+        let weight_count = connectivity.weight_chunk_count(neuron_count) * size_of::<NeuronChunk>() * 8;
+        let flips = read_indices(&mut file, weight_count, |_| Ok(()))?
+            .into_iter().map(|(i, _)| i).collect();
+        let read_activation = |file: &mut io::BufReader<fs::File>| Ok(Activation(file.read_i8()?));
+        let activations = read_indices(&mut file, neuron_count, read_activation)?;
+        let off_activations = match file.read_u8()? {
+            0 => None,
+            1 => Some(read_indices(&mut file, neuron_count, read_activation)?),
+            _ => return Err(invalid()),
+        };
+        let neuron_types = read_indices(&mut file, neuron_count, |file| match file.read_u8()? {
+            0 => Ok(NeuronType::Free),
+            1 => Ok(NeuronType::Excitatory),
+            2 => Ok(NeuronType::Inhibitory),
+            _ => Err(invalid()),
+        })?;
+
+        Ok(Patch {
+            neuron_count,
+            connectivity,
+            flips,
+            activations,
+            off_activations,
+            neuron_types,
+        })
+    }
+
+    /// Writes the patch into a file, where every change costs a few bytes.
+    /// Indices are stored as the LEB128 encoded distance to the previous index.
+    pub fn write_to_file<P: AsRef<Path>>(&self, target: P) -> io::Result<()> {
+        let mut file = io::BufWriter::new(fs::File::create(target)?);
+
+        file.write_u64::<LittleEndian>(self.neuron_count as u64)?;
+        self.connectivity.write_to(&mut file)?;
+
+        let flips: Vec<(usize, ())> = self.flips.iter().map(|&i| (i, ())).collect();
+        write_indices(&mut file, &flips, |_, _| Ok(()))?;
+        let write_activation = |file: &mut io::BufWriter<fs::File>, activation: &Activation| file.write_i8(activation.0);
+        write_indices(&mut file, &self.activations, write_activation)?;
+        match &self.off_activations {
+            Some(changes) => {
+                file.write_u8(1)?;
+                write_indices(&mut file, changes, write_activation)?;
+            }
+            None => file.write_u8(0)?,
+        }
+        write_indices(&mut file, &self.neuron_types, |file, neuron_type| file.write_u8(match neuron_type {
+            NeuronType::Free => 0,
+            NeuronType::Excitatory => 1,
+            NeuronType::Inhibitory => 2,
+        }))?;
+
+        file.flush()
+    }
+}
+
+/// Writes the amount of entries followed by every entry, as the distance to the previous index followed by its value.
+fn write_indices<W: io::Write, T>(writer: &mut W, entries: &[(usize, T)], mut write_value: impl FnMut(&mut W, &T) -> io::Result<()>) -> io::Result<()> {
+    write_varint(writer, entries.len() as u64)?;
+
+    let mut previous = 0;
+    for (i, value) in entries.iter() {
+        write_varint(writer, (i - previous) as u64)?;
+        write_value(writer, value)?;
+        previous = *i;
+    }
+
+    Ok(())
+}
+
+/// Reads entries written by `write_indices`, which have to be below `limit`.
+fn read_indices<R: io::Read, T>(reader: &mut R, limit: usize, mut read_value: impl FnMut(&mut R) -> io::Result<T>) -> io::Result<Vec<(usize, T)>> {
+    let count = read_varint(reader)?;
+
+    let mut entries = Vec::new();
+    let mut previous = 0usize;
+    for _ in 0..count {
+        let i = previous.checked_add(read_varint(reader)? as usize)
+            .filter(|&i| i < limit)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "index out of range"))?;
+        entries.push((i, read_value(reader)?));
+        previous = i;
+    }
+
+    Ok(entries)
+}
+
+fn write_varint<W: io::Write>(writer: &mut W, mut value: u64) -> io::Result<()> {
+    while value >= 0x80 {
+        writer.write_u8(value as u8 | 0x80)?;
+        value >>= 7;
+    }
+
+    writer.write_u8(value as u8)
+}
+
+fn read_varint<R: io::Read>(reader: &mut R) -> io::Result<u64> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let byte = reader.read_u8()?;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+
+    Err(io::Error::new(io::ErrorKind::InvalidData, "varint too long"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(base: &BinaryBrain, target: &BinaryBrain, name: &str) {
+        let patch = Patch::new(base, target).unwrap();
+        let mut patched = base.clone();
+        patch.apply(&mut patched).unwrap();
+        assert_eq!(&patched, target);
+
+        let path = std::env::temp_dir().join(format!("binary_brain_patch_{}_{}", std::process::id(), name));
+        patch.write_to_file(&path).unwrap();
+        let read = Patch::from_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(read, patch);
+
+        let mut patched = base.clone();
+        read.apply(&mut patched).unwrap();
+        assert_eq!(&patched, target);
+    }
+
+    fn mutate(brain: &BinaryBrain) -> BinaryBrain {
+        let mut rng = thread_rng();
+        let mut target = brain.clone();
+        for _ in 0..100 {
+            let position = rng.gen_range(0, target.weight_matrix.len() * size_of::<NeuronChunk>() * 8);
+            let weight = get_bit(&target.weight_matrix, position);
+            set_bit(&mut target.weight_matrix, position, !weight);
+        }
+        for i in (0..target.neuron_count).step_by(3) {
+            target.act[i] = Activation(target.act[i].0.saturating_add(20));
+        }
+        target
+    }
+
+    #[test]
+    fn rejects_mismatched_brains() {
+        let base = BinaryBrain::new(4, 4, 64).unwrap();
+        assert!(Patch::new(&base, &BinaryBrain::new(8, 2, 64).unwrap()).is_err());
+
+        let patch = Patch::new(&base, &mutate(&base)).unwrap();
+        let mut symmetric = BinaryBrain::new_with_connectivity(4, 4, 64, Connectivity::Symmetric).unwrap();
+        assert!(patch.apply(&mut symmetric).is_err());
+    }
+
+    #[test]
+    fn rejects_off_activations_above_activations() {
+        let mut base = BinaryBrain::new(4, 4, 64).unwrap();
+        base.set_off_activations(Some(vec![Activation(i8::MIN); base.neuron_count])).unwrap();
+        let mut target = mutate(&base);
+        target.set_off_activations(Some(target.act.clone())).unwrap();
+        let patch = Patch::new(&base, &target).unwrap();
+
+        // the patch raises the off activations to the activations of the target, which are above these
+        let mut other = base.clone();
+        other.act = vec![Activation(i8::MIN); other.neuron_count];
+        other.set_off_activations(Some(other.act.clone())).unwrap();
+        let unpatched = other.clone();
+        assert!(patch.apply(&mut other).is_err());
+        assert_eq!(other, unpatched);
+        assert_eq!(other.weight_matrix, unpatched.weight_matrix);
+    }
+
+    #[test]
+    fn round_trip_without_off_activations() {
+        let base = BinaryBrain::new(8, 8, 128).unwrap();
+        round_trip(&base, &mutate(&base), "without_off");
+    }
+
+    #[test]
+    fn round_trip_adding_off_activations() {
+        let base = BinaryBrain::new(8, 8, 128).unwrap();
+        let mut target = mutate(&base);
+        // off activations that equal the old activations of changed neurons are not part of the patch
+        let off_act = (0..target.neuron_count)
+            .map(|i| if i % 3 == 0 { base.act[i] } else { Activation(target.act[i].0.saturating_sub(10)) })
+            .collect();
+        target.set_off_activations(Some(off_act)).unwrap();
+        round_trip(&base, &target, "adding_off");
+    }
+
+    #[test]
+    fn round_trip_changing_off_activations() {
+        let mut base = BinaryBrain::new(8, 8, 128).unwrap();
+        let off_act = base.act.iter().map(|act| Activation(act.0.saturating_sub(30))).collect();
+        base.set_off_activations(Some(off_act)).unwrap();
+        let mut target = mutate(&base);
+        let off_act = target.act.iter().map(|act| Activation(act.0.saturating_sub(5))).collect();
+        target.set_off_activations(Some(off_act)).unwrap();
+        round_trip(&base, &target, "changing_off");
+        round_trip(&target, &base, "removing_off");
+    }
+}
@@ -0,0 +1,157 @@
+use crate::*;
+
+/// Result of `BinaryBrain::prune`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PruneReport {
+    /// Indices the removed neurons had before pruning.
+    pub removed: Vec<usize>,
+    pub neurons_before: usize,
+    pub neurons_after: usize,
+    /// The amount of weight matrix chunks before pruning.
+    pub weight_chunks_before: usize,
+    pub weight_chunks_after: usize,
+}
+
+/// The states and outputs of a brain while cycling with a sequence of inputs.
+struct Recording {
+    /// The state before the first cycle and after every cycle.
+    states: Vec<Vec<NeuronChunk>>,
+    outputs: Vec<Vec<(bool, i32)>>,
+}
+
+impl BinaryBrain {
+    /// Finds the hidden neurons that keep the same state while cycling a copy of this brain with `inputs`,
+    /// starting from its current state. Returns their indices along with the state they keep.
+    pub fn constant_neurons(&self, inputs: &[Vec<Activation>]) -> Result<Vec<(usize, bool)>> {
+        let states = self.clone().record(inputs)?.states;
+        let output_start = self.neuron_count - self.output_count;
+
+        Ok((self.input_count..output_start)
+            .filter(|&i| states.iter().all(|state| get_bit(state, i) == get_bit(&states[0], i)))
+            .map(|i| (i, get_bit(&states[0], i)))
+            .collect())
+    }
+
+    /// Removes hidden neurons that are constant while cycling with `inputs`, see `constant_neurons`.
+    /// The constant contribution of every removed neuron is subtracted from the activations of the remaining neurons,
+    /// so the output sums shift along with their activations.
+    /// Neurons are removed in multiples of 64, and only as many as possible while the pruned brain fires the same outputs
+    /// with the same distance to their activations over `inputs`. Activations that would leave their range are clamped,
+    /// which is what can make the outputs change.
+    /// Pruning needs deterministic dynamics, so `FiringMode::Threshold`, `NeuronModel::Binary` and no `UpdateOrder::Shuffled`,
+    /// and a brain without layers, whose masked weights would be compensated for as well.
+    pub fn prune(&mut self, inputs: &[Vec<Activation>]) -> Result<PruneReport> {
+        self.check_deterministic()?;
+        if self.layers.is_some() {
+            return Err(BinaryBrainError::UnsupportedDynamics);
+        }
+        match self.connectivity {
+            Connectivity::Dense | Connectivity::Symmetric => (),
+            _ => return Err(BinaryBrainError::UnsupportedConnectivity),
+        }
+
+        let candidates = self.constant_neurons(inputs)?;
+        let chunk_size = size_of::<NeuronChunk>() * 8;
+        let mut report = PruneReport {
+            removed: Vec::new(),
+            neurons_before: self.neuron_count,
+            neurons_after: self.neuron_count,
+            weight_chunks_before: self.weight_matrix.len(),
+            weight_chunks_after: self.weight_matrix.len(),
+        };
+
+        let mut count = candidates.len() / chunk_size * chunk_size;
+        while count > 0 {
+            let pruned = self.without_constant_neurons(&candidates[..count])?;
+
+            if self.same_outputs(&pruned, inputs)? {
+                report.removed = candidates[..count].iter().map(|&(i, _)| i).collect();
+                report.neurons_after = pruned.neuron_count;
+                report.weight_chunks_after = pruned.weight_matrix.len();
+                *self = pruned;
+                break;
+            }
+
+            count -= chunk_size;
+        }
+
+        Ok(report)
+    }
+
+    /// A copy of this brain without the given neurons, whose contribution in the given state is subtracted from the activations.
+    fn without_constant_neurons(&self, removed: &[(usize, bool)]) -> Result<BinaryBrain> {
+        let mut compensations = vec![0; self.neuron_count];
+        let mut is_removed = vec![false; self.neuron_count];
+        for &(j, state) in removed.iter() {
+            is_removed[j] = true;
+            for (i, compensation) in compensations.iter_mut().enumerate() {
+                if i != j {
+                    *compensation += if self.weight(i, j) == state { 1 } else { -1 };
+                }
+            }
+        }
+
+        let indices: Vec<usize> = removed.iter().map(|&(i, _)| i).collect();
+        let mut pruned = self.clone();
+        pruned.remove_neurons(&indices)?;
+
+        let compensate = |act: Activation, compensation: i32| {
+            Activation((act.0 as i32 - compensation).clamp(i8::MIN as i32, i8::MAX as i32) as i8)
+        };
+        let remaining = (0..self.neuron_count).filter(|&i| !is_removed[i]);
+        for (new, old) in remaining.enumerate() {
+            pruned.act[new] = compensate(self.act[old], compensations[old]);
+            if let (Some(pruned_off), Some(off_act)) = (pruned.off_act.as_mut(), self.off_act.as_ref()) {
+                pruned_off[new] = compensate(off_act[old], compensations[old]);
+            }
+        }
+
+        Ok(pruned)
+    }
+
+    /// Checks for `FiringMode::Threshold`, `NeuronModel::Binary` and no `UpdateOrder::Shuffled`,
+    /// which make cycling a brain with the same inputs from the same state fire the same neurons every time.
+    pub(crate) fn check_deterministic(&self) -> Result<()> {
+        if self.firing_mode != FiringMode::Threshold
+            || self.neuron_model != NeuronModel::Binary
+            || matches!(self.update_order, UpdateOrder::Shuffled(_)) {
+            return Err(BinaryBrainError::UnsupportedDynamics);
+        }
+
+        Ok(())
+    }
+
+    /// Whether copies of both brains fire the same outputs with the same distance to their activations
+    /// while cycling with `inputs`, starting from their current state.
+    pub(crate) fn same_outputs(&self, other: &BinaryBrain, inputs: &[Vec<Activation>]) -> Result<bool> {
+        let outputs = self.clone().record(inputs)?.outputs;
+        let other_outputs = other.clone().record(inputs)?.outputs;
+        let output_start = self.neuron_count - self.output_count;
+        let other_output_start = other.neuron_count - other.output_count;
+
+        Ok(self.output_count == other.output_count && outputs.iter().zip(other_outputs.iter()).all(|(output, other_output)| {
+            (0..self.output_count).all(|k| {
+                let margin = output[k].1 - self.act[output_start + k].0 as i32;
+                let other_margin = other_output[k].1 - other.act[other_output_start + k].0 as i32;
+                output[k].0 == other_output[k].0 && margin == other_margin
+            })
+        }))
+    }
+
+    fn record(&mut self, inputs: &[Vec<Activation>]) -> Result<Recording> {
+        let mut states = vec![self.values.clone()];
+        let mut outputs = Vec::with_capacity(inputs.len());
+        let mut output = Vec::with_capacity(self.output_count);
+
+        for input in inputs.iter() {
+            self.cycle(input, &mut output)?;
+            states.push(self.values.clone());
+            outputs.push(output.clone());
+        }
+
+        Ok(Recording {
+            states,
+            outputs,
+        })
+    }
+}
\ No newline at end of file
@@ -0,0 +1,273 @@
+use crate::*;
+
+/// How `BinaryBrain::add_neurons` initializes the parameters of new neurons.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum NeuronInit {
+    /// Random weights and activations, like a brain created by `BinaryBrain::new`.
+    Random,
+    /// New neurons come in pairs that have the same random incoming weights and activation
+    /// but opposite outgoing weights, so they cancel each other out while they fire together.
+    /// With `UpdateOrder::Synchronous` both neurons of a pair always fire together,
+    /// so the existing neurons behave exactly as before. Pairs are formed in index order,
+    /// and a pair with only one input neuron stops cancelling when that input is not zero.
+    /// Needs an even amount of new neurons. Only supported by `Connectivity::Dense`.
+    Cancelling,
+}
+
+impl BinaryBrain {
+    /// Adds `count` hidden neurons right before the output neurons, keeping all existing weights.
+    /// `count` has to be a multiple of 64 to keep the amount of neurons divisible by the chunk size.
+    pub fn add_neurons(&mut self, count: usize, init: NeuronInit) -> Result<()> {
+        self.add_neurons_with_rng(count, init, &mut thread_rng())
+    }
+
+    /// Like `add_neurons`, but draws the parameters of new neurons from `rng`.
+    pub fn add_neurons_with_rng<R: Rng + ?Sized>(&mut self, count: usize, init: NeuronInit, rng: &mut R) -> Result<()> {
+        let output_start = self.neuron_count - self.output_count;
+        let sources: Vec<Option<usize>> = (0..output_start).map(Some)
+            .chain((0..count).map(|_| None))
+            .chain((output_start..self.neuron_count).map(Some))
+            .collect();
+
+        self.remap(&sources, self.input_count, self.output_count, init, rng)
+    }
+
+    /// Removes the given neurons, keeping the weights between the remaining neurons.
+    /// Removed input and output neurons reduce the input and output count.
+    /// The amount of remaining neurons has to be a multiple of 64.
+    pub fn remove_neurons(&mut self, indices: &[usize]) -> Result<()> {
+        let removed = self.select(indices, 0..self.neuron_count)?;
+
+        let output_start = self.neuron_count - self.output_count;
+        let input_count = (0..self.input_count).filter(|&i| !removed[i]).count();
+        let output_count = (output_start..self.neuron_count).filter(|&i| !removed[i]).count();
+        let sources: Vec<Option<usize>> = (0..self.neuron_count)
+            .filter(|&i| !removed[i])
+            .map(Some)
+            .collect();
+
+        self.remap(&sources, input_count, output_count, NeuronInit::Random, &mut thread_rng())
+    }
+
+    /// Adds `count` input neurons after the existing ones, so they receive the last `count` values of the input.
+    /// New hidden neurons are added before the outputs to keep the amount of neurons a multiple of 64.
+    pub fn add_inputs(&mut self, count: usize, init: NeuronInit) -> Result<()> {
+        self.add_inputs_with_rng(count, init, &mut thread_rng())
+    }
+
+    /// Like `add_inputs`, but draws the parameters of new neurons from `rng`.
+    pub fn add_inputs_with_rng<R: Rng + ?Sized>(&mut self, count: usize, init: NeuronInit, rng: &mut R) -> Result<()> {
+        let output_start = self.neuron_count - self.output_count;
+        let sources: Vec<Option<usize>> = (0..self.input_count).map(Some)
+            .chain((0..count).map(|_| None))
+            .chain((self.input_count..output_start).map(Some))
+            .chain((0..padding(self.neuron_count + count)).map(|_| None))
+            .chain((output_start..self.neuron_count).map(Some))
+            .collect();
+
+        self.remap(&sources, self.input_count + count, self.output_count, init, rng)
+    }
+
+    /// Adds `count` output neurons after the existing ones, so they are the last `count` values of the output.
+    /// New hidden neurons are added before the outputs to keep the amount of neurons a multiple of 64.
+    pub fn add_outputs(&mut self, count: usize, init: NeuronInit) -> Result<()> {
+        self.add_outputs_with_rng(count, init, &mut thread_rng())
+    }
+
+    /// Like `add_outputs`, but draws the parameters of new neurons from `rng`.
+    pub fn add_outputs_with_rng<R: Rng + ?Sized>(&mut self, count: usize, init: NeuronInit, rng: &mut R) -> Result<()> {
+        let output_start = self.neuron_count - self.output_count;
+        let sources: Vec<Option<usize>> = (0..output_start).map(Some)
+            .chain((0..padding(self.neuron_count + count)).map(|_| None))
+            .chain((output_start..self.neuron_count).map(Some))
+            .chain((0..count).map(|_| None))
+            .collect();
+
+        self.remap(&sources, self.input_count, self.output_count + count, init, rng)
+    }
+
+    /// Turns the given input neurons into hidden neurons that keep their weights, right after the remaining inputs.
+    pub fn remove_inputs(&mut self, neurons: &[usize]) -> Result<()> {
+        let removed = self.select(neurons, 0..self.input_count)?;
+        let sources: Vec<Option<usize>> = (0..self.input_count).filter(|&i| !removed[i])
+            .chain(neurons.iter().copied())
+            .chain(self.input_count..self.neuron_count)
+            .map(Some)
+            .collect();
+
+        self.remap(&sources, self.input_count - neurons.len(), self.output_count, NeuronInit::Random, &mut thread_rng())
+    }
+
+    /// Turns the given output neurons into hidden neurons that keep their weights, right before the remaining outputs.
+    pub fn remove_outputs(&mut self, neurons: &[usize]) -> Result<()> {
+        let output_start = self.neuron_count - self.output_count;
+        let removed = self.select(neurons, output_start..self.neuron_count)?;
+        let sources: Vec<Option<usize>> = (0..output_start)
+            .chain(neurons.iter().copied())
+            .chain((output_start..self.neuron_count).filter(|&i| !removed[i]))
+            .map(Some)
+            .collect();
+
+        self.remap(&sources, self.input_count, self.output_count - neurons.len(), NeuronInit::Random, &mut thread_rng())
+    }
+
+    /// Marks the given neurons, which have to be unique and in `range`.
+    fn select(&self, neurons: &[usize], range: std::ops::Range<usize>) -> Result<Vec<bool>> {
+        let mut selected = vec![false; self.neuron_count];
+        for &i in neurons.iter() {
+            if !range.contains(&i) || selected[i] {
+                return Err(BinaryBrainError::InvalidNeuronIndex);
+            }
+            selected[i] = true;
+        }
+
+        Ok(selected)
+    }
+
+    /// Rebuilds the brain so neuron `i` takes the place of neuron `sources[i]`, or is a new neuron initialized by `init`
+    /// when it's `None`. Every neuron of the brain may appear at most once in `sources`.
+    pub(crate) fn remap<R: Rng + ?Sized>(
+        &mut self,
+        sources: &[Option<usize>],
+        input_count: usize,
+        output_count: usize,
+        init: NeuronInit,
+        rng: &mut R,
+    ) -> Result<()> {
+        let total_count = sources.len();
+        if !total_count.is_multiple_of(size_of::<NeuronChunk>() * 8) {
+            return Err(BinaryBrainError::TotalNotDivisbleByChunkSize);
+        }
+        if input_count + output_count > total_count {
+            return Err(BinaryBrainError::InputOutputAboveTotal);
+        }
+        match (&self.connectivity, init) {
+            (Connectivity::Dense, _) | (Connectivity::Symmetric, NeuronInit::Random) => (),
+            _ => return Err(BinaryBrainError::UnsupportedConnectivity),
+        }
+
+        // the second neuron of a cancelling pair copies the first one, which is stored here
+        let mut partner = vec![None; total_count];
+        if init == NeuronInit::Cancelling {
+            let new: Vec<usize> = (0..total_count).filter(|&i| sources[i].is_none()).collect();
+            if !new.len().is_multiple_of(2) {
+                return Err(BinaryBrainError::OddCancellingCount);
+            }
+            for pair in new.chunks(2) {
+                partner[pair[1]] = Some(pair[0]);
+            }
+        }
+
+        let mut weights = vec![NeuronChunk::default(); self.connectivity.weight_chunk_count(total_count)];
+        for to in 0..total_count {
+            for from in 0..total_count {
+                let position = match weight_position(&self.connectivity, total_count, to, from) {
+                    Some(position) => position,
+                    None => continue,
+                };
+
+                let weight = match (partner[to], partner[from], sources[to], sources[from]) {
+                    (Some(first), _, _, _) => weight_at(&self.connectivity, &weights, total_count, first, from),
+                    (None, Some(first), _, _) => !weight_at(&self.connectivity, &weights, total_count, to, first),
+                    (None, None, Some(old_to), Some(old_from)) => self.weight(old_to, old_from),
+                    _ => rng.gen(),
+                };
+                set_bit(&mut weights, position, weight);
+            }
+        }
+
+        let mut act = Vec::with_capacity(total_count);
+        for i in 0..total_count {
+            act.push(match (partner[i], sources[i]) {
+                (Some(first), _) => act[first],
+                (None, Some(old)) => self.act[old],
+                (None, None) => Activation(rng.gen()),
+            });
+        }
+
+        // new neurons behave like regular neurons
+        let off_act = self.off_act.as_ref().map(|off_act| {
+            (0..total_count).map(|i| sources[i].map_or(act[i], |old| off_act[old])).collect()
+        });
+
+        let mut values = vec![NeuronChunk::default(); total_count / (size_of::<NeuronChunk>() * 8)];
+        let mut type_mask = values.clone();
+        let mut type_sign = values.clone();
+        for (i, source) in sources.iter().enumerate() {
+            if let Some(old) = *source {
+                set_bit(&mut values, i, get_bit(&self.values, old));
+                set_bit(&mut type_mask, i, get_bit(&self.type_mask, old));
+                set_bit(&mut type_sign, i, get_bit(&self.type_sign, old));
+            }
+        }
+
+        let potential = sources.iter().map(|source| source.map_or(0.0, |old| self.potential[old])).collect();
+        let refractory = sources.iter().map(|source| source.map_or(0, |old| self.refractory[old])).collect();
+
+        // keep the relative order of the remaining neurons and update new neurons last
+        let update_order = match &self.update_order {
+            UpdateOrder::Permutation(permutation) => {
+                let mut new_index = vec![None; self.neuron_count];
+                for (i, source) in sources.iter().enumerate() {
+                    if let Some(old) = *source {
+                        new_index[old] = Some(i);
+                    }
+                }
+                let remaining = permutation.iter().filter_map(|&old| new_index[old]);
+                let new = (0..total_count).filter(|&i| sources[i].is_none());
+                UpdateOrder::Permutation(remaining.chain(new).collect())
+            }
+            order => order.clone(),
+        };
+
+        self.weight_matrix = weights;
+        self.act = act;
+        self.off_act = off_act;
+        self.values = values;
+        self.type_mask = type_mask;
+        self.type_sign = type_sign;
+        self.potential = potential;
+        self.refractory = refractory;
+        self.input_count = input_count;
+        self.output_count = output_count;
+        self.neuron_count = total_count;
+        self.set_update_order(update_order)?;
+        self.set_layers(None)?;
+        self.enforce_neuron_types();
+
+        Ok(())
+    }
+
+    /// The weight from neuron `from` to neuron `to`, `true` being +1.
+    /// Only supported by connectivities with a weight between every pair of neurons.
+    pub(crate) fn weight(&self, to: usize, from: usize) -> bool {
+        weight_at(&self.connectivity, &self.weight_matrix, self.neuron_count, to, from)
+    }
+}
+
+/// The amount of neurons to add to `total_count` neurons to make it a multiple of the chunk size.
+fn padding(total_count: usize) -> usize {
+    total_count.next_multiple_of(size_of::<NeuronChunk>() * 8) - total_count
+}
+
+/// Bit index of the weight from neuron `from` to neuron `to` in a dense or symmetric weight matrix,
+/// or `None` for the diagonal of a symmetric matrix and the lower triangle positions that mirror the upper one.
+pub(crate) fn weight_position(connectivity: &Connectivity, total_count: usize, to: usize, from: usize) -> Option<usize> {
+    let chunk_size = size_of::<NeuronChunk>() * 8;
+
+    match connectivity {
+        Connectivity::Dense => Some(to * total_count + from),
+        Connectivity::Symmetric if from < to => Some(triangle_offset(to) * chunk_size + from),
+        Connectivity::Symmetric => None,
+        _ => unreachable!("weights of grid neurons can't be addressed by neuron index"),
+    }
+}
+
+/// Like `weight_position`, but reads the weight and mirrors symmetric weights. The diagonal of a symmetric matrix reads as +1.
+fn weight_at(connectivity: &Connectivity, weights: &[NeuronChunk], total_count: usize, to: usize, from: usize) -> bool {
+    match connectivity {
+        Connectivity::Symmetric if to == from => true,
+        Connectivity::Symmetric if to < from => weight_at(connectivity, weights, total_count, from, to),
+        _ => get_bit(weights, weight_position(connectivity, total_count, to, from).unwrap()),
+    }
+}
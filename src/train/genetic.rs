@@ -2,6 +2,7 @@ use crate::*;
 use crate::util::Xoshiro128PlusPlusAvx2;
 // use rayon::prelude::*;
 use rand::distributions::{Distribution, Uniform};
+use rand::rngs::StdRng;
 use std::cmp::Ordering;
 use std::mem::transmute;
 
@@ -10,10 +11,22 @@ pub struct Genetic {
     p_mutate: u8,
     pop_select: Uniform<usize>,
     tournament_size: usize,
+    rng: StdRng,
 }
 
 impl Genetic {
     pub fn new(initial: BinaryBrain, pop_size: usize, tournament_size: usize, mutation: u8) -> Result<Genetic> {
+        Self::with_rng(initial, pop_size, tournament_size, mutation, StdRng::from_entropy())
+    }
+
+    /// Like `new`, but every random decision of the trainer is derived from `seed`.
+    /// Given the same initial brain, seed and a deterministic fitness function,
+    /// every generation is bit-identical between runs of the same build, including the seeds for stochastic firing.
+    pub fn with_seed(initial: BinaryBrain, pop_size: usize, tournament_size: usize, mutation: u8, seed: u64) -> Result<Genetic> {
+        Self::with_rng(initial, pop_size, tournament_size, mutation, StdRng::seed_from_u64(seed))
+    }
+
+    fn with_rng(initial: BinaryBrain, pop_size: usize, tournament_size: usize, mutation: u8, mut rng: StdRng) -> Result<Genetic> {
         if pop_size < 2 {
             return Err(BinaryBrainError::InvalidPopSize);
         }
@@ -24,7 +37,7 @@ impl Genetic {
         let mut pop = Vec::with_capacity(pop_size);
         pop.push((initial, f64::MIN));
         for _ in 0..pop_size - 1 {
-            pop.push((BinaryBrain::from_template(&pop[0].0), f64::MIN));
+            pop.push((BinaryBrain::from_template_with_rng(&pop[0].0, &mut rng), f64::MIN));
         }
 
         Ok(Genetic {
@@ -32,9 +45,13 @@ impl Genetic {
             p_mutate: mutation,
             pop_select: Uniform::new(0, pop_size),
             tournament_size: tournament_size,
+            rng,
         })
     }
 
+    /// Assigns a fitness to every brain in the population and returns the highest one.  
+    /// The fitness function is free to drive the brain with any of its cycle methods,
+    /// for example `BinaryBrain::cycle_clamped` to present bit inputs, which `evaluate_clamped` does for a fixed sequence.
     pub fn evaluate<F: FnMut(&mut BinaryBrain) -> f64>(&mut self, mut fitness: F) -> f64 {
         self.population.iter_mut().for_each(|p| {    
             p.1 = fitness(&mut p.0);
@@ -42,6 +59,48 @@ impl Genetic {
         self.population.iter().max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal)).unwrap().1
     }
 
+    /// Assigns every brain the fraction of target bits its outputs fire correctly over a sequence of bit inputs,
+    /// and returns the highest fraction. Every brain starts from a state where no neuron fired,
+    /// and every cycle clamps the input neurons to the next input with `BinaryBrain::cycle_clamped`.  
+    /// With `teacher_forcing` the output neurons are set to their targets after every cycle,
+    /// so the next cycle starts from the correct outputs instead of building on earlier mistakes.
+    pub fn evaluate_clamped(&mut self, inputs: &[Vec<bool>], targets: &[Vec<bool>], teacher_forcing: bool) -> Result<f64> {
+        let input_count = self.population[0].0.input_count();
+        let output_count = self.population[0].0.output_count();
+        if inputs.len() != targets.len() || inputs.iter().any(|input| input.len() != input_count) {
+            return Err(BinaryBrainError::WrongInputShape);
+        }
+        if targets.iter().any(|target| target.len() != output_count) {
+            return Err(BinaryBrainError::WrongOutputShape);
+        }
+
+        let mut clamps = Vec::with_capacity(input_count);
+        let mut output = Vec::with_capacity(output_count);
+        for (brain, fitness) in self.population.iter_mut() {
+            let mut state = vec![false; brain.activations().len()];
+            let output_start = state.len() - output_count;
+            brain.set_state(&state)?;
+
+            let mut correct = 0;
+            for (input, target) in inputs.iter().zip(targets.iter()) {
+                clamps.clear();
+                clamps.extend(input.iter().copied().enumerate());
+                brain.cycle_clamped(&clamps, &mut output)?;
+                correct += output.iter().zip(target.iter()).filter(|((fire, _), target)| fire == *target).count();
+
+                if teacher_forcing {
+                    state.copy_from_slice(&brain.state());
+                    state[output_start..].copy_from_slice(target);
+                    brain.set_state(&state)?;
+                }
+            }
+
+            *fitness = correct as f64 / (targets.len() * output_count).max(1) as f64;
+        }
+
+        Ok(self.population.iter().max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal)).unwrap().1)
+    }
+
     // TODO: somehow training fails to produce good results when using parallel evaluation when it works fine normally
 
     // pub fn evaluate_parallel<F: Fn(&mut BinaryBrain) -> f64 + Send + Sync>(&mut self, fitness: F) -> f64 {
@@ -59,17 +118,15 @@ impl Genetic {
         let input_count = self.population[0].0.input_count();
         let output_count = self.population[0].0.output_count();
 
-        let mut rng = thread_rng();
-        let mut tmp = [0; 64];
-        rng.fill_bytes(&mut tmp);
-        let mut bulk_rng = Xoshiro128PlusPlusAvx2::new(tmp);
+        let mut bulk_rng = Xoshiro128PlusPlusAvx2::from_rng(&mut self.rng);
 
+        let (population, pop_select, tournament_size, rng) = (&self.population, &self.pop_select, self.tournament_size, &mut self.rng);
         let mut run_tournament = || {
             let mut fit = [f64::MIN, f64::MIN];
-            let mut result = [&self.population[0].0, &self.population[0].0];
-            for _ in 0..self.tournament_size {
-                let idx = self.pop_select.sample(&mut rng);
-                let candidate = &self.population[idx];
+            let mut result = [&population[0].0, &population[0].0];
+            for _ in 0..tournament_size {
+                let idx = pop_select.sample(rng);
+                let candidate = &population[idx];
                 for i in 0..2 {
                     if candidate.1 > fit[i] {
                         fit[i] = candidate.1;
@@ -101,31 +158,47 @@ impl Genetic {
                 }
             }
 
-            for i in (0..act_count).step_by(32) {
-                let mutate_triggers: [i8; 32] = unsafe { transmute(bulk_rng.next()) };
-                let mutations: [i8; 32] = unsafe { transmute(bulk_rng.next()) };
-                let crossover: [i8; 32] = unsafe { transmute(bulk_rng.next()) };
-
-                for j in 0..32 {
-                    if mutate_triggers[j] > 127 - self.p_mutate as i8 {
-                        activations.0.push(Activation(mutations[j]));
-                        activations.1.push(Activation(mutations[(j + 1) % 32]));
-                    } else if crossover[j] > -1 {
-                        activations.0.push(parents[1].activations()[i + j]);
-                        activations.1.push(parents[0].activations()[i + j]);
-                    } else {
-                        activations.0.push(parents[0].activations()[i + j]);
-                        activations.1.push(parents[1].activations()[i + j]);
-                    }
-                }
+            breed_activations(&mut bulk_rng, self.p_mutate, parents[0].activations(), parents[1].activations(), &mut activations);
+
+            let connectivity = parents[0].connectivity();
+            let mut children = (
+                BinaryBrain::with_connectivity(connectivity.clone(), weights.0, activations.0, input_count, output_count).unwrap(),
+                BinaryBrain::with_connectivity(connectivity.clone(), weights.1, activations.1, input_count, output_count).unwrap(),
+            );
+            children.0.inherit_config(&population[0].0);
+            children.1.inherit_config(&population[0].0);
+            children.0.set_firing_seed(bulk_rng.next()[0]);
+            children.1.set_firing_seed(bulk_rng.next()[0]);
+
+            if let (Some(a), Some(b)) = (parents[0].off_activations(), parents[1].off_activations()) {
+                let mut off_activations = (Vec::with_capacity(act_count), Vec::with_capacity(act_count));
+                breed_activations(&mut bulk_rng, self.p_mutate, a, b, &mut off_activations);
+
+                // off activations are bred separately and may end up above the activations of the child
+                let limit = |off_act: Vec<Activation>, act: &[Activation]| -> Vec<Activation> {
+                    off_act.into_iter().zip(act.iter()).map(|(off, act)| Activation(off.0.min(act.0))).collect()
+                };
+                let off_activations = (
+                    limit(off_activations.0, children.0.activations()),
+                    limit(off_activations.1, children.1.activations()),
+                );
+                children.0.set_off_activations(Some(off_activations.0)).unwrap();
+                children.1.set_off_activations(Some(off_activations.1)).unwrap();
             }
 
-            new_pop.push((BinaryBrain::with_parameters(weights.0, activations.0, input_count, output_count).unwrap(), f64::MIN));
-            new_pop.push((BinaryBrain::with_parameters(weights.1, activations.1, input_count, output_count).unwrap(), f64::MIN));
+            new_pop.push((children.0, f64::MIN));
+            new_pop.push((children.1, f64::MIN));
         }
 
         self.population = new_pop;
     }
+
+    /// Clones the `count` fittest brains of the population, fittest first, for example to form an `Ensemble`.
+    pub fn clone_top(&self, count: usize) -> Vec<(BinaryBrain, f64)> {
+        let mut ranked: Vec<&(BinaryBrain, f64)> = self.population.iter().collect();
+        ranked.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal));
+        ranked.into_iter().take(count).cloned().collect()
+    }
         
     pub fn clone_fittest(self) -> (BinaryBrain, f64) {
         let target = self.population.iter().max_by(
@@ -137,6 +210,34 @@ impl Genetic {
 }
 
 
+fn breed_activations(
+    bulk_rng: &mut Xoshiro128PlusPlusAvx2,
+    p_mutate: u8,
+    a: &[Activation],
+    b: &[Activation],
+    children: &mut (Vec<Activation>, Vec<Activation>),
+) {
+    for i in (0..a.len()).step_by(32) {
+        let mutate_triggers: [i8; 32] = unsafe { transmute(bulk_rng.next()) };
+        let mutations: [i8; 32] = unsafe { transmute(bulk_rng.next()) };
+        let crossover: [i8; 32] = unsafe { transmute(bulk_rng.next()) };
+
+        for j in 0..32 {
+            if mutate_triggers[j] > 127 - p_mutate as i8 {
+                children.0.push(Activation(mutations[j]));
+                children.1.push(Activation(mutations[(j + 1) % 32]));
+            } else if crossover[j] > -1 {
+                children.0.push(b[i + j]);
+                children.1.push(a[i + j]);
+            } else {
+                children.0.push(a[i + j]);
+                children.1.push(b[i + j]);
+            }
+        }
+    }
+}
+
+
 #[cfg(test)]
 mod benches {
     use test::{Bencher};
@@ -0,0 +1,152 @@
+use crate::*;
+
+/// How `Hopfield` adjusts the weights for every stored pattern.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LearningRule {
+    /// Adds the product of the states of both neurons to each weight.
+    Hebbian,
+    /// The Storkey rule, which also subtracts the influence of the other neurons
+    /// and can store more patterns before they start to interfere.
+    Storkey,
+}
+
+/// Stores binary patterns as attractors of a symmetric brain, making it an associative memory.
+pub struct Hopfield {
+    // real valued symmetric weights with a zero diagonal, the brain only gets their sign
+    weights: Vec<f32>,
+    neuron_count: usize,
+    pattern_count: usize,
+    rule: LearningRule,
+}
+
+impl Hopfield {
+    pub fn new(neuron_count: usize, rule: LearningRule) -> Result<Hopfield> {
+        if !neuron_count.is_multiple_of(size_of::<NeuronChunk>() * 8) {
+            return Err(BinaryBrainError::TotalNotDivisbleByChunkSize);
+        }
+
+        Ok(Hopfield {
+            weights: vec![0.0; neuron_count * neuron_count],
+            neuron_count,
+            pattern_count: 0,
+            rule,
+        })
+    }
+
+    pub fn store(&mut self, pattern: &[bool]) -> Result<()> {
+        if pattern.len() != self.neuron_count {
+            return Err(BinaryBrainError::WrongStateShape);
+        }
+
+        let n = self.neuron_count;
+        let x: Vec<f32> = pattern.iter().map(|&fired| if fired { 1.0 } else { -1.0 }).collect();
+
+        match self.rule {
+            LearningRule::Hebbian => {
+                for i in 0..n {
+                    for j in 0..n {
+                        if i != j {
+                            self.weights[i * n + j] += x[i] * x[j];
+                        }
+                    }
+                }
+            }
+            LearningRule::Storkey => {
+                // local fields of the current weights, the diagonal is zero so no neuron counts itself
+                let h: Vec<f32> = (0..n).map(|i| {
+                    self.weights[i * n..(i + 1) * n].iter().zip(x.iter()).map(|(w, x)| w * x).sum()
+                }).collect();
+
+                let mut delta = vec![0.0; n * n];
+                for i in 0..n {
+                    for j in 0..n {
+                        if i != j {
+                            let h_ij = h[i] - self.weights[i * n + j] * x[j];
+                            let h_ji = h[j] - self.weights[j * n + i] * x[i];
+                            delta[i * n + j] = (x[i] * x[j] - x[i] * h_ji - h_ij * x[j]) / n as f32;
+                        }
+                    }
+                }
+                self.weights.iter_mut().zip(delta.iter()).for_each(|(w, d)| *w += d);
+            }
+        }
+
+        self.pattern_count += 1;
+        Ok(())
+    }
+
+    #[inline]
+    pub fn pattern_count(&self) -> usize {
+        self.pattern_count
+    }
+
+    /// Creates a symmetric brain from the sign of the learned weights, a weight of exactly zero becomes +1.  
+    /// The brain has no inputs and every neuron is an output, all activations are 0.
+    pub fn brain(&self) -> BinaryBrain {
+        let chunk_size = size_of::<NeuronChunk>() * 8;
+        let n = self.neuron_count;
+        let connectivity = Connectivity::Symmetric;
+
+        let mut weights = Vec::with_capacity(connectivity.weight_chunk_count(n));
+        for i in 0..n {
+            for start in (0..i).step_by(chunk_size) {
+                let mut chunk = 0;
+                for j in start..i.min(start + chunk_size) {
+                    if self.weights[i * n + j] >= 0.0 {
+                        chunk |= 1 << (j - start);
+                    }
+                }
+                weights.push(NeuronChunk(chunk));
+            }
+        }
+        weights.resize(connectivity.weight_chunk_count(n), NeuronChunk::default());
+
+        BinaryBrain::with_connectivity(connectivity, weights, vec![Activation(0); n], 0, n).unwrap()
+    }
+
+    /// Starts `brain` from `pattern` and cycles it with zero input until it settles or `max_cycles` is reached.
+    /// Returns the state it ended in.
+    pub fn recall(brain: &mut BinaryBrain, pattern: &[bool], max_cycles: usize) -> Result<(Vec<bool>, Settle)> {
+        brain.set_state(pattern)?;
+
+        let input = vec![Activation(0); brain.input_count()];
+        let mut output = Vec::with_capacity(brain.output_count());
+        let settle = brain.cycle_until_stable(&input, &mut output, max_cycles)?;
+
+        Ok((brain.state(), settle))
+    }
+}
+
+
+#[cfg(test)]
+mod benches {
+    use test::{Bencher};
+    use super::*;
+
+    #[bench]
+    fn hopfield_store_512(b: &mut Bencher) {
+        let mut rng = thread_rng();
+        let pattern: Vec<bool> = (0..512).map(|_| rng.gen()).collect();
+        let mut trainer = Hopfield::new(512, LearningRule::Storkey).unwrap();
+
+        b.iter(|| {
+            trainer.store(&pattern).unwrap();
+        });
+    }
+
+    #[bench]
+    fn hopfield_recall_512(b: &mut Bencher) {
+        let mut rng = thread_rng();
+        let mut trainer = Hopfield::new(512, LearningRule::Hebbian).unwrap();
+        for _ in 0..16 {
+            let pattern: Vec<bool> = (0..512).map(|_| rng.gen()).collect();
+            trainer.store(&pattern).unwrap();
+        }
+        let mut brain = trainer.brain();
+        let probe: Vec<bool> = (0..512).map(|_| rng.gen()).collect();
+
+        b.iter(|| {
+            Hopfield::recall(&mut brain, &probe, 64).unwrap()
+        });
+    }
+}
\ No newline at end of file
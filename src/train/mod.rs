@@ -1,3 +1,7 @@
 mod genetic;
+mod hopfield;
+mod readout;
 
-pub use self::genetic::Genetic;
\ No newline at end of file
+pub use self::genetic::Genetic;
+pub use self::hopfield::{Hopfield, LearningRule};
+pub use self::readout::{Readout, Reservoir};
\ No newline at end of file
@@ -0,0 +1,221 @@
+use crate::*;
+
+/// A linear readout from the hidden neurons of a fixed brain to real valued targets, fitted with ridge regression.
+/// This uses the brain as an echo state reservoir: the brain is never changed, only the readout learns.
+/// Every hidden neuron is a feature of +1 when it fired and -1 otherwise, plus a constant bias feature.
+pub struct Readout {
+    feature_count: usize,
+    target_count: usize,
+    ridge: f64,
+    // sums over the recorded samples of the products of every pair of features, lower triangle only,
+    // and of every feature with every target
+    feature_products: Vec<f64>,
+    target_products: Vec<f64>,
+    sample_count: usize,
+    // one row of feature weights per target, the last weight of a row is the bias
+    weights: Vec<f64>,
+}
+
+impl Readout {
+    /// Creates a readout for the hidden neurons of `brain` with `target_count` targets.
+    /// `ridge` is added to the diagonal of the feature products when fitting, so larger values keep the weights smaller.
+    pub fn new(brain: &BinaryBrain, target_count: usize, ridge: f64) -> Result<Readout> {
+        if !(ridge >= 0.0 && ridge.is_finite()) {
+            return Err(BinaryBrainError::InvalidReadout);
+        }
+
+        let feature_count = hidden_count(brain) + 1;
+        Ok(Readout {
+            feature_count,
+            target_count,
+            ridge,
+            feature_products: vec![0.0; feature_count * feature_count],
+            target_products: vec![0.0; feature_count * target_count],
+            sample_count: 0,
+            weights: vec![0.0; target_count * feature_count],
+        })
+    }
+
+    /// Records the current state of the hidden neurons of `brain` along with the targets it should map to.
+    pub fn record(&mut self, brain: &BinaryBrain, target: &[f64]) -> Result<()> {
+        if target.len() != self.target_count {
+            return Err(BinaryBrainError::WrongOutputShape);
+        }
+
+        let x = self.features(brain)?;
+        let n = self.feature_count;
+        for i in 0..n {
+            for j in 0..=i {
+                self.feature_products[i * n + j] += x[i] * x[j];
+            }
+            for (k, y) in target.iter().enumerate() {
+                self.target_products[i * self.target_count + k] += x[i] * y;
+            }
+        }
+
+        self.sample_count += 1;
+        Ok(())
+    }
+
+    /// Cycles `brain` with every input and records its state after each cycle with the matching target.
+    /// The first `washout` cycles are not recorded, so the state has time to forget where it started.
+    pub fn record_sequence(&mut self, brain: &mut BinaryBrain, inputs: &[Vec<Activation>], targets: &[Vec<f64>], washout: usize) -> Result<()> {
+        if inputs.len() != targets.len() {
+            return Err(BinaryBrainError::WrongOutputShape);
+        }
+
+        let mut output = Vec::with_capacity(brain.output_count());
+        for (cycle, (input, target)) in inputs.iter().zip(targets.iter()).enumerate() {
+            brain.cycle(input, &mut output)?;
+            if cycle >= washout {
+                self.record(brain, target)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Fits the weights to all recorded samples by solving the regularized normal equations with a Cholesky decomposition.
+    /// The feature products take `(hidden neurons + 1)^2` floats and fitting takes time cubic in the amount of hidden neurons.
+    /// Fails when no sample was recorded, or when the ridge is 0 and the recorded states don't determine every weight.
+    pub fn fit(&mut self) -> Result<()> {
+        if self.sample_count == 0 {
+            return Err(BinaryBrainError::InvalidReadout);
+        }
+
+        let n = self.feature_count;
+        let mut l = self.feature_products.clone();
+        // the bias is not regularized
+        for i in 0..n - 1 {
+            l[i * n + i] += self.ridge;
+        }
+
+        for j in 0..n {
+            let diagonal = l[j * n + j] - (0..j).map(|k| l[j * n + k] * l[j * n + k]).sum::<f64>();
+            if diagonal <= f64::EPSILON * self.sample_count as f64 {
+                return Err(BinaryBrainError::InvalidReadout);
+            }
+            l[j * n + j] = diagonal.sqrt();
+
+            for i in j + 1..n {
+                let dot: f64 = (0..j).map(|k| l[i * n + k] * l[j * n + k]).sum();
+                l[i * n + j] = (l[i * n + j] - dot) / l[j * n + j];
+            }
+        }
+
+        for t in 0..self.target_count {
+            // forward substitution with L, then back substitution with its transpose
+            let mut z = vec![0.0; n];
+            for i in 0..n {
+                let dot: f64 = (0..i).map(|k| l[i * n + k] * z[k]).sum();
+                z[i] = (self.target_products[i * self.target_count + t] - dot) / l[i * n + i];
+            }
+            let w = &mut self.weights[t * n..(t + 1) * n];
+            for i in (0..n).rev() {
+                let dot: f64 = (i + 1..n).map(|k| l[k * n + i] * w[k]).sum();
+                w[i] = (z[i] - dot) / l[i * n + i];
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Maps the current state of the hidden neurons of `brain` to the targets with the fitted weights.
+    pub fn predict(&self, brain: &BinaryBrain) -> Result<Vec<f64>> {
+        let x = self.features(brain)?;
+
+        Ok(self.weights.chunks(self.feature_count)
+            .map(|row| row.iter().zip(x.iter()).map(|(w, x)| w * x).sum())
+            .collect())
+    }
+
+    #[inline]
+    pub fn sample_count(&self) -> usize {
+        self.sample_count
+    }
+
+    #[inline]
+    pub fn target_count(&self) -> usize {
+        self.target_count
+    }
+
+    /// The fitted weights, a row of one weight per hidden neuron followed by the bias for every target.
+    pub fn weights(&self) -> &[f64] {
+        &self.weights
+    }
+
+    fn features(&self, brain: &BinaryBrain) -> Result<Vec<f64>> {
+        if hidden_count(brain) + 1 != self.feature_count {
+            return Err(BinaryBrainError::MismatchedBrains);
+        }
+
+        let hidden = brain.input_count..brain.neuron_count - brain.output_count;
+        Ok(hidden.map(|i| if get_bit(&brain.values, i) { 1.0 } else { -1.0 })
+            .chain(std::iter::once(1.0))
+            .collect())
+    }
+}
+
+/// A fixed brain with a fitted readout, which predicts the targets after every cycle.
+pub struct Reservoir {
+    brain: BinaryBrain,
+    readout: Readout,
+    output: Vec<(bool, i32)>,
+}
+
+impl Reservoir {
+    pub fn new(brain: BinaryBrain, readout: Readout) -> Result<Reservoir> {
+        if hidden_count(&brain) + 1 != readout.feature_count {
+            return Err(BinaryBrainError::MismatchedBrains);
+        }
+
+        Ok(Reservoir {
+            brain,
+            readout,
+            output: Vec::new(),
+        })
+    }
+
+    /// Cycles the brain with `input` and predicts the targets from its new state.
+    pub fn step(&mut self, input: &[Activation]) -> Result<Vec<f64>> {
+        self.brain.cycle(input, &mut self.output)?;
+        self.readout.predict(&self.brain)
+    }
+
+    pub fn brain(&self) -> &BinaryBrain {
+        &self.brain
+    }
+
+    pub fn readout(&self) -> &Readout {
+        &self.readout
+    }
+
+    pub fn into_parts(self) -> (BinaryBrain, Readout) {
+        (self.brain, self.readout)
+    }
+}
+
+fn hidden_count(brain: &BinaryBrain) -> usize {
+    brain.neuron_count - brain.input_count - brain.output_count
+}
+
+
+#[cfg(test)]
+mod benches {
+    use test::{Bencher};
+    use super::*;
+
+    #[bench]
+    fn readout_fit_256(b: &mut Bencher) {
+        let mut rng = thread_rng();
+        let mut brain = BinaryBrain::new(8, 0, 256).unwrap();
+        let mut readout = Readout::new(&brain, 2, 1.0).unwrap();
+        let inputs: Vec<Vec<Activation>> = (0..64).map(|_| (0..8).map(|_| Activation(rng.gen())).collect()).collect();
+        let targets: Vec<Vec<f64>> = (0..64).map(|_| vec![rng.gen(), rng.gen()]).collect();
+        readout.record_sequence(&mut brain, &inputs, &targets, 8).unwrap();
+
+        b.iter(|| {
+            readout.fit().unwrap();
+        });
+    }
+}
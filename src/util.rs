@@ -1,8 +1,9 @@
 use std::mem;
+use rand::Rng;
 #[cfg(all(target_feature = "avx2", target_arch = "x86_64"))]
 use core::arch::x86_64::*;
 
-#[derive(Clone)]
+#[derive(Debug, Clone)]
 pub struct Xoshiro128PlusPlusAvx2 {
     state: [[u64; Self::UNROLL]; 2]
 }
@@ -17,13 +18,19 @@ impl Xoshiro128PlusPlusAvx2 {
         } }
     }
 
+    pub fn from_rng<R: Rng + ?Sized>(rng: &mut R) -> Xoshiro128PlusPlusAvx2 {
+        let mut state = [0; 8 * Self::UNROLL * 2];
+        rng.fill_bytes(&mut state);
+        Self::new(state)
+    }
+
     /// Generates 256 bits
     #[inline]
     pub fn next(&mut self) -> [u64; Self::UNROLL] {
         let mut s1: [u64; Self::UNROLL] = unsafe { mem::MaybeUninit::uninit().assume_init() };
         let mut result: [u64; Self::UNROLL] = unsafe { mem::MaybeUninit::uninit().assume_init() };
 
-        (0..Self::UNROLL).for_each(|i| result[i] = (self.state[0][i] + self.state[1][i]).rotate_left(17) + self.state[0][i]);
+        (0..Self::UNROLL).for_each(|i| result[i] = self.state[0][i].wrapping_add(self.state[1][i]).rotate_left(17).wrapping_add(self.state[0][i]));
         (0..Self::UNROLL).for_each(|i| s1[i] = self.state[0][i] ^ self.state[1][i]);
         (0..Self::UNROLL).for_each(|i| self.state[0][i] = self.state[0][i].rotate_left(49) ^ s1[i] ^ (s1[i] << 21));
         (0..Self::UNROLL).for_each(|i| self.state[1][i] = s1[i].rotate_left(28));
@@ -62,14 +69,15 @@ impl Xoshiro128PlusPlusAvx2 {
             let mut result = [0i8; Self::UNROLL * 8];
             let treshold = [(127 - p) as i8; Self::UNROLL * 8];
             
+            // same steps as the avx2 version, so both generate the same bits from the same state
             for _ in 0..8 {
-                (0..Self::UNROLL * 8).for_each(|i| result[i] += result[i]);
+                (0..Self::UNROLL * 8).for_each(|i| result[i] = result[i].wrapping_add(result[i]));
                 
                 let random: [i8; Self::UNROLL * 8] = unsafe { mem::transmute(self.next()) };
-                let mut compare: [i8; Self::UNROLL * 8] = unsafe { mem::MaybeUninit::uninit().assume_init() };
+                let mut compare = [0i8; Self::UNROLL * 8];
                 (0..Self::UNROLL * 8).for_each(|i| compare[i] = if random[i] > treshold[i] {-1} else {0});
                     
-                (0..Self::UNROLL * 8).for_each(|i| result[i] -= random[i]);
+                (0..Self::UNROLL * 8).for_each(|i| result[i] = result[i].wrapping_sub(compare[i]));
             }
 
             return unsafe { mem::transmute(result) };